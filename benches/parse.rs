@@ -0,0 +1,93 @@
+use std::io::Cursor;
+
+use abootimg_oxide::{Header, HeaderV0, HeaderV0Versioned, OsVersionPatch};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+const PAGE_SIZE: u32 = 4096;
+
+/// Builds a v2 boot image with a kernel of `kernel_size` bytes and smaller other sections.
+fn synthetic_image(kernel_size: u32) -> Vec<u8> {
+    let ramdisk_size = kernel_size / 2;
+    let second_size = kernel_size / 16;
+    let recovery_dtbo_size = 1000;
+    let dtb_size = 5000;
+
+    let hdr = Header::V0(HeaderV0 {
+        kernel_size,
+        kernel_addr: 0x10008000,
+        ramdisk_size,
+        ramdisk_addr: 0x11000000,
+        second_bootloader_size: second_size,
+        second_bootloader_addr: 0x10f00000,
+        tags_addr: 0x10000100,
+        page_size: PAGE_SIZE,
+        osversionpatch: OsVersionPatch(0),
+        board_name: [0; 16],
+        cmdline_part_1: Box::new([0; 512]),
+        hash_digest: [0; 32],
+        cmdline_part_2: Box::new([0; 1024]),
+        versioned: HeaderV0Versioned::V2 {
+            recovery_dtbo_size,
+            recovery_dtbo_addr: 0,
+            dtb_size,
+            dtb_addr: 0x11f00000,
+        },
+        trailing: Vec::new(),
+    });
+
+    let mut image = Vec::new();
+    hdr.write(&mut image).unwrap();
+    for section in hdr.sections() {
+        image.resize(section.position, 0);
+        image.extend((0..section.size).map(|i| i as u8));
+    }
+    image.resize(image.len().next_multiple_of(PAGE_SIZE as usize), 0);
+    image
+}
+
+fn bench(c: &mut Criterion) {
+    let sizes = [64 * 1024, 1024 * 1024, 16 * 1024 * 1024];
+    let images = sizes.map(|size| (size, synthetic_image(size)));
+
+    let mut group = c.benchmark_group("parse");
+    for (size, image) in &images {
+        group.bench_with_input(BenchmarkId::from_parameter(size), image, |b, image| {
+            b.iter(|| Header::parse(&mut Cursor::new(image)).unwrap())
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("extract_all");
+    for (size, image) in &images {
+        group.throughput(Throughput::Bytes(image.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), image, |b, image| {
+            b.iter(|| {
+                let mut r = Cursor::new(image);
+                let hdr = Header::parse(&mut r).unwrap();
+                hdr.extract_all(&mut r).unwrap()
+            })
+        });
+    }
+    group.finish();
+
+    #[cfg(feature = "hashing")]
+    {
+        let mut group = c.benchmark_group("compute_digest");
+        for (size, image) in &images {
+            let mut r = Cursor::new(image);
+            let hdr = Header::parse(&mut r).unwrap();
+            let extracted = hdr.extract_all(&mut r).unwrap();
+            let Header::V0(hdr) = hdr else {
+                unreachable!("synthetic images are v2");
+            };
+            group.throughput(Throughput::Bytes(image.len() as u64));
+            group.bench_with_input(BenchmarkId::from_parameter(size), &extracted, |b, image| {
+                b.iter(|| hdr.compute_digest(image))
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
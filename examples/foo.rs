@@ -1,10 +1,9 @@
 use std::{
     fs::File,
-    io::{self, BufWriter, Read, Seek, SeekFrom},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom},
 };
 
 use abootimg_oxide::Header;
-use binrw::io::BufReader;
 
 fn main() {
     let mut r = BufReader::new(File::open("/home/axel/nordce-update/boot_a.img").unwrap());
@@ -13,8 +12,8 @@ fn main() {
 
     println!("kpos {}", hdr.kernel_position());
     let mut w = BufWriter::new(File::create("boot_a_kernel").unwrap());
-    let r = r.get_mut();
+    // Seek the BufReader itself, which discards its buffer, rather than the inner File
     r.seek(SeekFrom::Start(hdr.kernel_position() as u64))
         .unwrap();
-    io::copy(&mut r.take(hdr.kernel_size() as u64), w.get_mut()).unwrap();
+    io::copy(&mut r.take(hdr.kernel_size() as u64), &mut w).unwrap();
 }
@@ -1,3 +1,101 @@
-fn main() {
-    println!("Hello, world!");
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use abootimg_oxide::{ImageWriter, Manifest, OsPatch, OsVersion, OsVersionPatch};
+use clap::Parser;
+
+/// Repacks a boot image unpacked by `unpack_bootimg`
+///
+/// The sections are read from the directory containing the manifest.
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// Path to the manifest.json written by `unpack_bootimg`
+    #[arg(long)]
+    manifest: PathBuf,
+
+    /// Path of the boot image to write
+    #[arg(long)]
+    output: PathBuf,
+
+    /// OS version, e.g. 13.0.0, replacing the one in the manifest
+    #[arg(long)]
+    os_version: Option<OsVersion>,
+
+    /// OS patch level, e.g. 2024-12, replacing the one in the manifest
+    #[arg(long)]
+    os_patch: Option<OsPatch>,
+
+    /// Don't append the vendor tag recorded in the manifest, e.g. SEANDROIDENFORCE,
+    /// after the sections. Some bootloaders refuse images without it
+    #[arg(long = "no-preserve-trailing", action = clap::ArgAction::SetFalse)]
+    preserve_trailing: bool,
+}
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Args) -> Result<(), String> {
+    let mut manifest: Manifest = serde_json::from_slice(
+        &fs::read(&args.manifest)
+            .map_err(|err| format!("failed to read {}: {err}", args.manifest.display()))?,
+    )
+    .map_err(|err| format!("failed to parse {}: {err}", args.manifest.display()))?;
+    let osversionpatch = OsVersionPatch(manifest.osversionpatch);
+    manifest.osversionpatch = OsVersionPatch::new(
+        args.os_version.unwrap_or(osversionpatch.version()),
+        args.os_patch.unwrap_or(osversionpatch.patch()),
+    )
+    .0;
+    let dir = args.manifest.parent().unwrap_or(".".as_ref());
+
+    let mut sections = Vec::new();
+    for section in &mut manifest.sections {
+        if section.size == 0 {
+            continue;
+        }
+        let path = dir.join(section.kind.name());
+        let data =
+            fs::read(&path).map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+        if data.len() != section.size as usize {
+            eprintln!(
+                "warning: {} is {} bytes, but the manifest records {} bytes",
+                section.kind,
+                data.len(),
+                section.size
+            );
+            section.size = u32::try_from(data.len())
+                .map_err(|_| format!("{} is too large", path.display()))?;
+        }
+        sections.push((section.kind, data));
+    }
+    let hdr = manifest.to_header().map_err(|err| err.to_string())?;
+
+    let file = File::create(&args.output)
+        .map_err(|err| format!("failed to create {}: {err}", args.output.display()))?;
+    let mut w = ImageWriter::new(BufWriter::new(file));
+    w.write_header(&hdr).map_err(|err| err.to_string())?;
+    for (kind, data) in &sections {
+        w.write_section(*kind, data)
+            .map_err(|err| err.to_string())?;
+    }
+    let mut file = w.finish().map_err(|err| err.to_string())?;
+    (|| {
+        if let Some(tag) = manifest.trailing_tag.filter(|_| args.preserve_trailing) {
+            file.write_all(tag.bytes())?;
+        }
+        file.flush()
+    })()
+    .map_err(|err| format!("failed to write {}: {err}", args.output.display()))
 }
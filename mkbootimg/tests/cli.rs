@@ -0,0 +1,148 @@
+use std::{
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use abootimg_oxide::{Header, HeaderV3, Manifest, OsPatch, OsVersion, OsVersionPatch};
+
+fn mkbootimg() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_mkbootimg"))
+}
+
+/// Returns a command running `unpack_bootimg`, built next to `mkbootimg`.
+fn unpack_bootimg() -> Command {
+    let path = Path::new(env!("CARGO_BIN_EXE_mkbootimg")).with_file_name("unpack_bootimg");
+    if !path.exists() {
+        let status = Command::new(env!("CARGO"))
+            .args(["build", "-p", "unpack_bootimg"])
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+    Command::new(path)
+}
+
+/// Returns a fresh directory for a test.
+fn test_dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Writes a v3 manifest with a kernel and ramdisk to `dir`, like `unpack_bootimg`.
+fn unpacked_v3(dir: &Path) -> PathBuf {
+    let hdr = Header::V3(HeaderV3 {
+        kernel_size: 3000,
+        ramdisk_size: 10,
+        osversionpatch: OsVersionPatch(0),
+        cmdline: Box::new([0; 1536]),
+        v4_signature_size: None,
+        trailing: Vec::new(),
+    });
+    fs::write(dir.join("kernel"), [1; 3000]).unwrap();
+    fs::write(dir.join("ramdisk"), [2; 10]).unwrap();
+    let manifest = dir.join("manifest.json");
+    fs::write(
+        &manifest,
+        serde_json::to_vec(&Manifest::from_header(&hdr)).unwrap(),
+    )
+    .unwrap();
+    manifest
+}
+
+#[test]
+fn os_version_patch() {
+    let dir = test_dir("os_version_patch");
+    let manifest = unpacked_v3(&dir);
+    let image = dir.join("boot.img");
+
+    let output = mkbootimg()
+        .arg("--manifest")
+        .arg(&manifest)
+        .arg("--output")
+        .arg(&image)
+        .args(["--os-version", "13.0.0", "--os-patch", "2024-12"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let hdr = Header::parse(&mut Cursor::new(fs::read(&image).unwrap())).unwrap();
+    assert_eq!(
+        hdr.osversionpatch(),
+        OsVersionPatch::new(OsVersion::new(13, 0, 0), OsPatch::new(2024, 12))
+    );
+}
+
+#[test]
+fn malformed_os_patch() {
+    let dir = test_dir("malformed_os_patch");
+    let manifest = unpacked_v3(&dir);
+
+    let output = mkbootimg()
+        .arg("--manifest")
+        .arg(&manifest)
+        .arg("--output")
+        .arg(dir.join("boot.img"))
+        .args(["--os-patch", "2024-13"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("invalid value `2024-13`, expected YYYY-MM"),
+        "{stderr}"
+    );
+    assert!(!dir.join("boot.img").exists());
+}
+
+#[test]
+fn preserve_trailing() {
+    let dir = test_dir("preserve_trailing");
+    let hdr = Header::V3(HeaderV3 {
+        kernel_size: 3000,
+        ramdisk_size: 10,
+        osversionpatch: OsVersionPatch(0),
+        cmdline: Box::new([0; 1536]),
+        v4_signature_size: None,
+        trailing: Vec::new(),
+    });
+    let mut image = Vec::new();
+    hdr.write(&mut image).unwrap();
+    image.resize(4096, 0);
+    image.resize(4096 + 3000, 1);
+    image.resize(2 * 4096, 0);
+    image.resize(2 * 4096 + 10, 2);
+    image.resize(3 * 4096, 0);
+    let untagged = image.clone();
+    image.extend(b"SEANDROIDENFORCE");
+    let original = dir.join("boot.img");
+    fs::write(&original, &image).unwrap();
+
+    let out = dir.join("out");
+    let status = unpack_bootimg()
+        .arg("--boot_img")
+        .arg(&original)
+        .arg("--out")
+        .arg(&out)
+        .arg("-q")
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    for (args, expected) in [(&[][..], &image), (&["--no-preserve-trailing"], &untagged)] {
+        let repacked = dir.join("repacked.img");
+        let output = mkbootimg()
+            .arg("--manifest")
+            .arg(out.join("manifest.json"))
+            .arg("--output")
+            .arg(&repacked)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        assert!(fs::read(&repacked).unwrap() == *expected);
+    }
+}
@@ -0,0 +1,217 @@
+use std::{
+    fmt,
+    io::{Seek, SeekFrom, Write},
+};
+
+use crate::{Error, Header, HeaderV0, HeaderV0Versioned};
+
+/// Physical load addresses of a v0-v2 boot image's sections
+///
+/// See [`HeaderV0::load_addresses`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LoadAddresses {
+    /// Kernel physical load address
+    pub kernel: u32,
+    /// Ramdisk physical load address
+    pub ramdisk: u32,
+    /// Second stage bootloader physical load address
+    pub second: u32,
+    /// Kernel tags physical load address
+    pub tags: u32,
+    /// Recovery DTBO/ACPIO physical load address, `None` at version 0
+    pub recovery_dtbo: Option<u64>,
+    /// DTB physical load address, `None` at versions 0 and 1
+    pub dtb: Option<u64>,
+}
+
+/// Load address field of a v0-v2 header, for [`Header::rewrite_field`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum HeaderField {
+    /// Kernel physical load address
+    KernelAddr,
+    /// Ramdisk physical load address
+    RamdiskAddr,
+    /// Second stage bootloader physical load address
+    SecondAddr,
+    /// Kernel tags physical load address
+    TagsAddr,
+}
+impl HeaderField {
+    /// Returns the field's offset in the header, including the magic.
+    #[must_use]
+    pub fn offset(self) -> u64 {
+        match self {
+            Self::KernelAddr => 12,
+            Self::RamdiskAddr => 20,
+            Self::SecondAddr => 28,
+            Self::TagsAddr => 32,
+        }
+    }
+}
+impl fmt::Display for HeaderField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::KernelAddr => "kernel_addr",
+            Self::RamdiskAddr => "ramdisk_addr",
+            Self::SecondAddr => "second_addr",
+            Self::TagsAddr => "tags_addr",
+        })
+    }
+}
+
+impl HeaderV0 {
+    /// Sets the kernel physical load address.
+    pub fn set_kernel_addr(&mut self, addr: u32) {
+        self.kernel_addr = addr;
+    }
+    /// Sets the ramdisk physical load address.
+    pub fn set_ramdisk_addr(&mut self, addr: u32) {
+        self.ramdisk_addr = addr;
+    }
+    /// Sets the second stage bootloader physical load address.
+    pub fn set_second_addr(&mut self, addr: u32) {
+        self.second_bootloader_addr = addr;
+    }
+    /// Sets the kernel tags physical load address.
+    pub fn set_tags_addr(&mut self, addr: u32) {
+        self.tags_addr = addr;
+    }
+    /// Returns the physical load addresses of every section.
+    #[must_use]
+    pub fn load_addresses(&self) -> LoadAddresses {
+        let (recovery_dtbo, dtb) = match self.versioned {
+            HeaderV0Versioned::V0 => (None, None),
+            HeaderV0Versioned::V1 {
+                recovery_dtbo_addr, ..
+            } => (Some(recovery_dtbo_addr), None),
+            HeaderV0Versioned::V2 {
+                recovery_dtbo_addr,
+                dtb_addr,
+                ..
+            } => (Some(recovery_dtbo_addr), Some(dtb_addr)),
+        };
+        LoadAddresses {
+            kernel: self.kernel_addr,
+            ramdisk: self.ramdisk_addr,
+            second: self.second_bootloader_addr,
+            tags: self.tags_addr,
+            recovery_dtbo,
+            dtb,
+        }
+    }
+}
+
+impl Header {
+    /// Sets a load address and patches only its 4 bytes in the image at `writer`,
+    /// leaving the rest of the image untouched.
+    ///
+    /// The header is only changed once the write succeeds, so it keeps matching the
+    /// image if the write fails.
+    ///
+    /// The image must start at the beginning of `writer`. Any signature covering the
+    /// header, e.g. AVB, won't match afterwards. Fails with
+    /// [`Error::FieldNotInVersion`] for v3-v4 headers, which have no load addresses.
+    pub fn rewrite_field<W: Write + Seek>(
+        &mut self,
+        writer: &mut W,
+        field: HeaderField,
+        value: u32,
+    ) -> Result<(), Error> {
+        let Self::V0(hdr) = self else {
+            return Err(Error::FieldNotInVersion {
+                field,
+                version: self.header_version(),
+            });
+        };
+        writer.seek(SeekFrom::Start(field.offset()))?;
+        writer.write_all(&value.to_le_bytes())?;
+        match field {
+            HeaderField::KernelAddr => hdr.set_kernel_addr(value),
+            HeaderField::RamdiskAddr => hdr.set_ramdisk_addr(value),
+            HeaderField::SecondAddr => hdr.set_second_addr(value),
+            HeaderField::TagsAddr => hdr.set_tags_addr(value),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{build_image, header_v0, header_v3};
+
+    #[test]
+    fn load_addresses() {
+        let hdr = header_v0(HeaderV0Versioned::V2 {
+            recovery_dtbo_size: 0,
+            recovery_dtbo_addr: 0x1000,
+            dtb_size: 0,
+            dtb_addr: 0x1_1f00_0000,
+        });
+        assert_eq!(
+            hdr.load_addresses(),
+            LoadAddresses {
+                kernel: hdr.kernel_addr,
+                ramdisk: hdr.ramdisk_addr,
+                second: hdr.second_bootloader_addr,
+                tags: hdr.tags_addr,
+                recovery_dtbo: Some(0x1000),
+                dtb: Some(0x1_1f00_0000),
+            }
+        );
+
+        let hdr = header_v0(HeaderV0Versioned::V0);
+        assert_eq!(hdr.load_addresses().recovery_dtbo, None);
+        assert_eq!(hdr.load_addresses().dtb, None);
+    }
+
+    #[test]
+    fn rewrite_ramdisk_addr() {
+        let mut hdr = header_v0(HeaderV0Versioned::V0);
+        hdr.kernel_size = 100;
+        hdr.ramdisk_size = 10;
+        let mut hdr = Header::V0(hdr);
+        let original = build_image(&hdr);
+        let mut image = std::io::Cursor::new(original.clone());
+
+        hdr.rewrite_field(&mut image, HeaderField::RamdiskAddr, 0x12000000)
+            .unwrap();
+        let image = image.into_inner();
+        let parsed = Header::parse(&mut std::io::Cursor::new(&image)).unwrap();
+        assert_eq!(parsed, hdr);
+        let Header::V0(v0) = &parsed else {
+            unreachable!()
+        };
+        assert_eq!(v0.ramdisk_addr, 0x12000000);
+        let changed: Vec<_> = (0..image.len())
+            .filter(|i| image[*i] != original[*i])
+            .collect();
+        assert!(changed.iter().all(|i| (20..24).contains(i)), "{changed:?}");
+
+        // A failed write leaves the header alone
+        let before = hdr.clone();
+        let mut short = [0; 22];
+        assert!(matches!(
+            hdr.rewrite_field(
+                &mut std::io::Cursor::new(&mut short[..]),
+                HeaderField::RamdiskAddr,
+                0x13000000
+            ),
+            Err(Error::Io(_))
+        ));
+        assert_eq!(hdr, before);
+
+        assert!(matches!(
+            Header::V3(header_v3(0, 0)).rewrite_field(
+                &mut std::io::Cursor::new(Vec::new()),
+                HeaderField::KernelAddr,
+                0
+            ),
+            Err(Error::FieldNotInVersion {
+                field: HeaderField::KernelAddr,
+                version: 3
+            })
+        ));
+    }
+}
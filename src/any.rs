@@ -0,0 +1,147 @@
+use std::{
+    fmt,
+    io::{Read, Seek},
+};
+
+use crate::{peek_magic, take_until_null, Error, Header, HeaderV0Versioned, VendorHeader};
+
+/// Header of a boot, recovery or vendor boot image
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AnyHeader {
+    /// Boot or recovery image header, with the `ANDROID!` magic
+    Boot(Header),
+    /// Vendor boot image header, with the `VNDRBOOT` magic
+    Vendor(VendorHeader),
+}
+
+/// Kind of image a header belongs to
+///
+/// See [`AnyHeader::kind`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ImageCategory {
+    /// Boot image
+    Boot,
+    /// Recovery image of a non-A/B device
+    Recovery,
+    /// Vendor boot image
+    VendorBoot,
+    /// Image without a kernel or recovery hints, e.g. an `init_boot` image
+    Unknown,
+}
+
+impl fmt::Display for ImageCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Boot => "boot",
+            Self::Recovery => "recovery",
+            Self::VendorBoot => "vendor_boot",
+            Self::Unknown => "unknown",
+        })
+    }
+}
+
+impl AnyHeader {
+    /// Parses a boot or vendor boot image header, telling them apart by their magic.
+    pub fn parse<R: Read + Seek>(reader: &mut R) -> Result<Self, Error> {
+        if peek_magic(reader, 0)?.starts_with(VendorHeader::MAGIC) {
+            Ok(Self::Vendor(VendorHeader::parse(reader)?))
+        } else {
+            Ok(Self::Boot(Header::parse(reader)?))
+        }
+    }
+    /// Returns the kind of image the header belongs to.
+    ///
+    /// Vendor boot images are known from their magic. The rest is a guess from the
+    /// header: a v1-v2 image with a recovery DTBO/ACPIO, which `mkbootimg` only adds to
+    /// recovery images, or with "recovery" in its command line or board name is a
+    /// recovery image. Otherwise, it's a boot image if it has a kernel.
+    #[must_use]
+    pub fn kind(&self) -> ImageCategory {
+        let hdr = match self {
+            Self::Vendor(_) => return ImageCategory::VendorBoot,
+            Self::Boot(hdr) => hdr,
+        };
+        let mentions_recovery = |bytes: &[u8]| {
+            bytes
+                .windows(8)
+                .any(|w| w.eq_ignore_ascii_case(b"recovery"))
+        };
+        if mentions_recovery(&hdr.cmdline()) {
+            return ImageCategory::Recovery;
+        }
+        if let Header::V0(v0) = hdr {
+            if let HeaderV0Versioned::V1 {
+                recovery_dtbo_size, ..
+            }
+            | HeaderV0Versioned::V2 {
+                recovery_dtbo_size, ..
+            } = v0.versioned
+            {
+                if recovery_dtbo_size != 0 {
+                    return ImageCategory::Recovery;
+                }
+            }
+            if mentions_recovery(take_until_null(&v0.board_name)) {
+                return ImageCategory::Recovery;
+            }
+        }
+        if hdr.kernel_size() != 0 {
+            ImageCategory::Boot
+        } else {
+            ImageCategory::Unknown
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        tests::{build_image, header_v0, header_v3},
+        vendor::tests::vendor_header,
+    };
+
+    #[test]
+    fn vendor_kind() {
+        let mut image = Cursor::new(Vec::new());
+        binrw::BinWrite::write(&vendor_header(None), &mut image).unwrap();
+        image.set_position(0);
+        let hdr = AnyHeader::parse(&mut image).unwrap();
+        assert!(matches!(hdr, AnyHeader::Vendor(_)));
+        assert_eq!(hdr.kind(), ImageCategory::VendorBoot);
+    }
+
+    #[test]
+    fn recovery_kind() {
+        let mut hdr = header_v0(HeaderV0Versioned::V1 {
+            recovery_dtbo_size: 0,
+            recovery_dtbo_addr: 0,
+        });
+        hdr.kernel_size = 100;
+        let boot = AnyHeader::parse(&mut Cursor::new(build_image(&Header::V0(hdr.clone()))));
+        assert_eq!(boot.unwrap().kind(), ImageCategory::Boot);
+
+        let mut recovery = hdr.clone();
+        recovery.versioned = HeaderV0Versioned::V1 {
+            recovery_dtbo_size: 64,
+            recovery_dtbo_addr: 0,
+        };
+        let recovery = AnyHeader::parse(&mut Cursor::new(build_image(&Header::V0(recovery))));
+        assert_eq!(recovery.unwrap().kind(), ImageCategory::Recovery);
+
+        hdr.board_name[..8].copy_from_slice(b"Recovery");
+        assert_eq!(
+            AnyHeader::Boot(Header::V0(hdr)).kind(),
+            ImageCategory::Recovery
+        );
+
+        // init_boot
+        assert_eq!(
+            AnyHeader::Boot(Header::V3(header_v3(0, 100))).kind(),
+            ImageCategory::Unknown
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+use binrw::{binread, BinRead};
+
+use crate::{take_until_null, Error};
+
+/// Android Verified Boot (AVB) vbmeta image header
+///
+/// Only the fields needed to locate the descriptors are read.
+#[derive(BinRead, Debug)]
+#[br(big, magic = b"AVB0")]
+struct VbmetaHeader {
+    #[br(pad_before = 8)]
+    authentication_data_block_size: u64,
+    #[br(pad_after = 68)]
+    _auxiliary_data_block_size: u64,
+    descriptors_offset: u64,
+    descriptors_size: u64,
+}
+impl VbmetaHeader {
+    const SIZE: u64 = 256;
+}
+
+/// AVB footer, found in the last 64 bytes of a partition image signed by `avbtool
+/// add_hash_footer`
+#[derive(BinRead, Clone, Debug, PartialEq, Eq, Hash)]
+#[br(big, magic = b"AVBf")]
+pub struct AvbFooter {
+    /// Major version of the footer format
+    pub version_major: u32,
+    /// Minor version of the footer format
+    pub version_minor: u32,
+    /// Size of the image before the vbmeta image and footer were appended
+    pub original_image_size: u64,
+    /// Offset of the appended vbmeta image
+    pub vbmeta_offset: u64,
+    /// Size of the appended vbmeta image
+    #[br(pad_after = 28)]
+    pub vbmeta_size: u64,
+}
+impl AvbFooter {
+    /// Size of the footer in bytes
+    pub const SIZE: u64 = 64;
+
+    /// Reads the footer from the last [`SIZE`](Self::SIZE) bytes of a reader.
+    ///
+    /// Returns `None` if the reader is too short or doesn't end with a footer.
+    pub fn read_from_end<R: Read + Seek>(reader: &mut R) -> Result<Option<Self>, Error> {
+        let len = reader.seek(SeekFrom::End(0))?;
+        let Some(position) = len.checked_sub(Self::SIZE) else {
+            return Ok(None);
+        };
+        reader.seek(SeekFrom::Start(position))?;
+        let mut footer = [0; Self::SIZE as usize];
+        reader.read_exact(&mut footer)?;
+        if !footer.starts_with(b"AVBf") {
+            return Ok(None);
+        }
+        Ok(Some(Self::read(&mut Cursor::new(footer))?))
+    }
+}
+
+/// AVB hash descriptor, describing the digest of a partition
+#[binread]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[br(big)]
+pub struct HashDescriptor {
+    /// Size of the hashed image
+    pub image_size: u64,
+    /// Hash algorithm, e.g. `sha256`
+    #[br(map = |raw: [u8; 32]| String::from_utf8_lossy(take_until_null(&raw)).into_owned())]
+    pub hash_algorithm: String,
+    #[br(temp)]
+    partition_name_len: u32,
+    #[br(temp)]
+    salt_len: u32,
+    #[br(temp, pad_after = 4 + 60)]
+    digest_len: u32,
+    /// Name of the hashed partition, e.g. `boot`
+    #[br(count = partition_name_len, try_map = String::from_utf8)]
+    pub partition_name: String,
+    /// Salt prepended to the image when hashing
+    #[br(count = salt_len)]
+    pub salt: Vec<u8>,
+    /// Digest of the salted image
+    #[br(count = digest_len)]
+    pub digest: Vec<u8>,
+}
+impl HashDescriptor {
+    const TAG: u64 = 2;
+
+    /// Returns the first hash descriptor in a vbmeta image, such as a v4 boot
+    /// signature.
+    ///
+    /// Returns `None` if the vbmeta image has no hash descriptors.
+    pub fn find_first(vbmeta: &[u8]) -> Result<Option<Self>, Error> {
+        let mut r = Cursor::new(vbmeta);
+        let header = VbmetaHeader::read(&mut r)?;
+
+        // The descriptors are in the auxiliary data block, which follows the
+        // authentication data block
+        let start = VbmetaHeader::SIZE
+            .saturating_add(header.authentication_data_block_size)
+            .saturating_add(header.descriptors_offset);
+        let end = start.saturating_add(header.descriptors_size);
+        let mut pos = start;
+        while pos < end {
+            r.seek(SeekFrom::Start(pos))?;
+            let [tag, num_bytes_following] = <[u64; 2]>::read_be(&mut r)?;
+            if tag == Self::TAG {
+                return Ok(Some(Self::read(&mut r)?));
+            }
+            pos = (pos + 16).saturating_add(num_bytes_following);
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Builds a vbmeta image with the given descriptors and no authentication data.
+    pub(crate) fn vbmeta(descriptors: &[u8]) -> Vec<u8> {
+        let mut image = b"AVB0".to_vec();
+        image.extend([0; 8]);
+        image.extend(0u64.to_be_bytes());
+        image.extend((descriptors.len() as u64).to_be_bytes());
+        image.resize(96, 0);
+        image.extend(0u64.to_be_bytes());
+        image.extend((descriptors.len() as u64).to_be_bytes());
+        image.resize(256, 0);
+        image.extend(descriptors);
+        image
+    }
+
+    pub(crate) fn hash_descriptor(partition_name: &str, salt: &[u8], digest: &[u8]) -> Vec<u8> {
+        let mut body = 4096u64.to_be_bytes().to_vec();
+        let mut hash_algorithm = [0; 32];
+        hash_algorithm[..6].copy_from_slice(b"sha256");
+        body.extend(hash_algorithm);
+        for len in [partition_name.len(), salt.len(), digest.len(), 0] {
+            body.extend((len as u32).to_be_bytes());
+        }
+        body.extend([0; 60]);
+        body.extend(partition_name.as_bytes());
+        body.extend(salt);
+        body.extend(digest);
+        body.resize(body.len().next_multiple_of(8), 0);
+
+        let mut descriptor = HashDescriptor::TAG.to_be_bytes().to_vec();
+        descriptor.extend((body.len() as u64).to_be_bytes());
+        descriptor.extend(body);
+        descriptor
+    }
+
+    #[test]
+    fn first_hash_descriptor() {
+        // Property descriptor, which should be skipped
+        let mut descriptors = 0u64.to_be_bytes().to_vec();
+        descriptors.extend(8u64.to_be_bytes());
+        descriptors.extend(*b"foo=bar\0");
+        descriptors.extend(hash_descriptor("boot", &[1, 2, 3], &[0xaa; 32]));
+
+        let descriptor = HashDescriptor::find_first(&vbmeta(&descriptors))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            descriptor,
+            HashDescriptor {
+                image_size: 4096,
+                hash_algorithm: "sha256".to_owned(),
+                partition_name: "boot".to_owned(),
+                salt: vec![1, 2, 3],
+                digest: vec![0xaa; 32],
+            }
+        );
+    }
+
+    /// Builds an AVB footer.
+    pub(crate) fn footer(
+        original_image_size: u64,
+        vbmeta_offset: u64,
+        vbmeta_size: u64,
+    ) -> Vec<u8> {
+        let mut footer = b"AVBf".to_vec();
+        footer.extend(1u32.to_be_bytes());
+        footer.extend(0u32.to_be_bytes());
+        for field in [original_image_size, vbmeta_offset, vbmeta_size] {
+            footer.extend(field.to_be_bytes());
+        }
+        footer.resize(AvbFooter::SIZE as usize, 0);
+        footer
+    }
+
+    #[test]
+    fn read_footer() {
+        let mut image = vec![0; 8192];
+        assert_eq!(
+            AvbFooter::read_from_end(&mut Cursor::new(&image)).unwrap(),
+            None
+        );
+        image.extend(footer(4096, 4096, 1600));
+        assert_eq!(
+            AvbFooter::read_from_end(&mut Cursor::new(&image)).unwrap(),
+            Some(AvbFooter {
+                version_major: 1,
+                version_minor: 0,
+                original_image_size: 4096,
+                vbmeta_offset: 4096,
+                vbmeta_size: 1600,
+            })
+        );
+        assert_eq!(
+            AvbFooter::read_from_end(&mut Cursor::new(&image[8192 + 60..])).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn no_descriptors() {
+        assert_eq!(HashDescriptor::find_first(&vbmeta(&[])).unwrap(), None);
+        assert!(HashDescriptor::find_first(&[0; 256]).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{Header, HeaderV0, HeaderV3, OsVersionPatch};
+
+/// Fields and section positions shared by every boot image header version
+///
+/// This is implemented for [`HeaderV0`], [`HeaderV3`] and [`Header`], so generic code
+/// can take an `impl BootImageHeader` instead of matching on [`Header`].
+pub trait BootImageHeader {
+    /// Returns the boot image header's version number.
+    fn header_version(&self) -> u32;
+    /// Returns the boot image header's OS version and patch level.
+    fn osversionpatch(&self) -> OsVersionPatch;
+    /// Returns the page size in bytes.
+    fn page_size(&self) -> usize;
+    /// Returns the kernel's size.
+    fn kernel_size(&self) -> u32;
+    /// Returns the kernel's position in the boot image.
+    fn kernel_position(&self) -> usize;
+    /// Returns the ramdisk's size.
+    fn ramdisk_size(&self) -> u32;
+    /// Returns the ramdisk's position in the boot image.
+    fn ramdisk_position(&self) -> usize;
+}
+
+impl BootImageHeader for HeaderV0 {
+    fn header_version(&self) -> u32 {
+        HeaderV0::header_version(self)
+    }
+    fn osversionpatch(&self) -> OsVersionPatch {
+        self.osversionpatch
+    }
+    fn page_size(&self) -> usize {
+        self.page_size as usize
+    }
+    fn kernel_size(&self) -> u32 {
+        self.kernel_size
+    }
+    fn kernel_position(&self) -> usize {
+        HeaderV0::kernel_position(self)
+    }
+    fn ramdisk_size(&self) -> u32 {
+        self.ramdisk_size
+    }
+    fn ramdisk_position(&self) -> usize {
+        HeaderV0::ramdisk_position(self)
+    }
+}
+
+impl BootImageHeader for HeaderV3 {
+    fn header_version(&self) -> u32 {
+        HeaderV3::header_version(self)
+    }
+    fn osversionpatch(&self) -> OsVersionPatch {
+        self.osversionpatch
+    }
+    fn page_size(&self) -> usize {
+        HeaderV3::PAGE_SIZE
+    }
+    fn kernel_size(&self) -> u32 {
+        self.kernel_size
+    }
+    fn kernel_position(&self) -> usize {
+        HeaderV3::kernel_position()
+    }
+    fn ramdisk_size(&self) -> u32 {
+        self.ramdisk_size
+    }
+    fn ramdisk_position(&self) -> usize {
+        HeaderV3::ramdisk_position(self)
+    }
+}
+
+impl BootImageHeader for Header {
+    fn header_version(&self) -> u32 {
+        Header::header_version(self)
+    }
+    fn osversionpatch(&self) -> OsVersionPatch {
+        Header::osversionpatch(self)
+    }
+    fn page_size(&self) -> usize {
+        Header::page_size(self)
+    }
+    fn kernel_size(&self) -> u32 {
+        Header::kernel_size(self)
+    }
+    fn kernel_position(&self) -> usize {
+        Header::kernel_position(self)
+    }
+    fn ramdisk_size(&self) -> u32 {
+        Header::ramdisk_size(self)
+    }
+    fn ramdisk_position(&self) -> usize {
+        Header::ramdisk_position(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::{header_v0, header_v3},
+        HeaderV0Versioned,
+    };
+
+    /// Returns the offset just past the ramdisk, excluding padding.
+    fn ramdisk_end(hdr: &impl BootImageHeader) -> usize {
+        hdr.ramdisk_position() + hdr.ramdisk_size() as usize
+    }
+
+    #[test]
+    fn generic() {
+        let mut v0 = header_v0(HeaderV0Versioned::V0);
+        v0.kernel_size = 5000;
+        v0.ramdisk_size = 100;
+        assert_eq!(ramdisk_end(&v0), 4 * 2048 + 100);
+        assert_eq!(ramdisk_end(&Header::V0(v0)), 4 * 2048 + 100);
+
+        let v3 = header_v3(5000, 100);
+        assert_eq!(ramdisk_end(&v3), 3 * 4096 + 100);
+        assert_eq!(BootImageHeader::page_size(&v3), 4096);
+        assert_eq!(ramdisk_end(&Header::V3(v3)), 3 * 4096 + 100);
+    }
+}
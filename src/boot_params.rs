@@ -0,0 +1,86 @@
+use crate::{take_until_null, Header, VendorHeader};
+
+/// Returns the parameters the kernel sees at runtime, merging the boot and vendor
+/// command lines with `bootconfig` entries, in that order.
+///
+/// Command line tokens are split at the first `=`, and flags without one get an
+/// empty value. Bootconfig keys under `kernel.` are passed to the kernel without
+/// that prefix. A later parameter overrides the value of an earlier one with the
+/// same key, keeping its place.
+#[must_use]
+pub fn effective_boot_params(
+    boot: &Header,
+    vendor: &VendorHeader,
+    bootconfig: &[(String, String)],
+) -> Vec<(String, String)> {
+    let mut params: Vec<(String, String)> = Vec::new();
+    let boot_cmdline = boot.cmdline();
+    let cmdline_params = cmdline_tokens(&boot_cmdline)
+        .chain(cmdline_tokens(take_until_null(&*vendor.cmdline)))
+        .map(|token| {
+            let token = String::from_utf8_lossy(token);
+            match token.split_once('=') {
+                Some((key, value)) => (key.to_owned(), value.trim_matches('"').to_owned()),
+                None => (token.into_owned(), String::new()),
+            }
+        });
+    let bootconfig_params = bootconfig.iter().map(|(key, value)| {
+        let key = key.strip_prefix("kernel.").unwrap_or(key);
+        (key.to_owned(), value.clone())
+    });
+    for (key, value) in cmdline_params.chain(bootconfig_params) {
+        match params.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = value,
+            None => params.push((key, value)),
+        }
+    }
+    params
+}
+
+/// Splits a command line at whitespace outside double quotes, like the kernel.
+fn cmdline_tokens(cmdline: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut in_quotes = false;
+    cmdline
+        .split(move |b| {
+            if *b == b'"' {
+                in_quotes = !in_quotes;
+            }
+            b.is_ascii_whitespace() && !in_quotes
+        })
+        .filter(|token| !token.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::header_v3, vendor::tests::vendor_header};
+
+    #[test]
+    fn merge_cmdline_and_bootconfig() {
+        let mut boot = header_v3(0, 0);
+        boot.set_cmdline(b"console=ttyMSM0 quiet loglevel=4")
+            .unwrap();
+        let mut vendor = vendor_header(None);
+        let vendor_cmdline = b"androidboot.hardware=qcom msg=\"a b\"";
+        vendor.cmdline[..vendor_cmdline.len()].copy_from_slice(vendor_cmdline);
+        let bootconfig = [
+            ("androidboot.hardware".into(), "sm8550".into()),
+            ("kernel.loglevel".into(), "7".into()),
+            ("androidboot.slot_suffix".into(), "_a".into()),
+        ];
+
+        let params = effective_boot_params(&Header::V3(boot), &vendor, &bootconfig);
+        assert_eq!(
+            params,
+            [
+                ("console", "ttyMSM0"),
+                ("quiet", ""),
+                ("loglevel", "7"),
+                ("androidboot.hardware", "sm8550"),
+                ("msg", "a b"),
+                ("androidboot.slot_suffix", "_a"),
+            ]
+            .map(|(k, v)| (k.to_owned(), v.to_owned()))
+        );
+    }
+}
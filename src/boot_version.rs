@@ -0,0 +1,65 @@
+use std::fmt;
+
+use crate::Error;
+
+/// Boot image header version, from 0 to 4
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BootImageVersion(u32);
+impl BootImageVersion {
+    /// Returns the version number.
+    #[must_use]
+    pub fn get(self) -> u32 {
+        self.0
+    }
+    /// Returns `true` for versions 0-2, which are parsed as [`HeaderV0`](crate::HeaderV0).
+    #[must_use]
+    pub fn is_v0_family(self) -> bool {
+        self.0 <= 2
+    }
+    /// Returns `true` for versions 3-4, which are parsed as [`HeaderV3`](crate::HeaderV3).
+    #[must_use]
+    pub fn is_v3_family(self) -> bool {
+        self.0 >= 3
+    }
+}
+
+impl TryFrom<u32> for BootImageVersion {
+    type Error = Error;
+
+    fn try_from(version: u32) -> Result<Self, Self::Error> {
+        match version {
+            0..=4 => Ok(Self(version)),
+            _ => Err(Error::UnknownHeaderVersion { version }),
+        }
+    }
+}
+
+impl From<BootImageVersion> for u32 {
+    fn from(version: BootImageVersion) -> Self {
+        version.0
+    }
+}
+
+impl fmt::Display for BootImageVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test() {
+    for version in 0..=2 {
+        let version = BootImageVersion::try_from(version).unwrap();
+        assert!(version.is_v0_family() && !version.is_v3_family());
+    }
+    for version in 3..=4 {
+        let version = BootImageVersion::try_from(version).unwrap();
+        assert!(!version.is_v0_family() && version.is_v3_family());
+    }
+    assert_eq!(BootImageVersion::try_from(4).unwrap().to_string(), "4");
+    assert!(matches!(
+        BootImageVersion::try_from(5),
+        Err(Error::UnknownHeaderVersion { version: 5 })
+    ));
+}
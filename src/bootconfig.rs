@@ -0,0 +1,134 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::{Error, VendorHeader};
+
+/// Trailer the kernel expects after bootconfig parameters
+///
+/// ```text
+/// params | size (u32 LE) | checksum (u32 LE) | "#BOOTCONFIG\n"
+/// ```
+///
+/// See [`VendorHeader::bootconfig_trailer`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BootconfigTrailer {
+    /// Size of the parameters before the trailer
+    pub params_size: u32,
+    /// Sum of the parameters' bytes
+    pub checksum: u32,
+}
+impl BootconfigTrailer {
+    /// Magic at the very end of the trailer
+    pub const MAGIC: &'static [u8; 12] = b"#BOOTCONFIG\n";
+    /// Size of the trailer, including the magic
+    pub const SIZE: usize = 20;
+
+    /// Parses the trailer at the end of a bootconfig blob, or returns `None` if the
+    /// blob doesn't end with one.
+    #[must_use]
+    pub fn parse(bootconfig: &[u8]) -> Option<Self> {
+        let trailer = bootconfig.get(bootconfig.len().checked_sub(Self::SIZE)?..)?;
+        let (fields, magic) = trailer.split_at(8);
+        if magic != Self::MAGIC {
+            return None;
+        }
+        Some(Self {
+            params_size: u32::from_le_bytes(fields[..4].try_into().unwrap()),
+            checksum: u32::from_le_bytes(fields[4..].try_into().unwrap()),
+        })
+    }
+    /// Computes the checksum of bootconfig parameters, the wrapping sum of their
+    /// bytes.
+    #[must_use]
+    pub fn checksum_of(params: &[u8]) -> u32 {
+        params
+            .iter()
+            .fold(0u32, |sum, b| sum.wrapping_add(u32::from(*b)))
+    }
+    /// Returns `true` if `params` has the size and checksum recorded in the trailer.
+    #[must_use]
+    pub fn matches(&self, params: &[u8]) -> bool {
+        params.len() == self.params_size as usize && Self::checksum_of(params) == self.checksum
+    }
+}
+
+impl VendorHeader {
+    /// Reads the trailer at the end of the bootconfig section.
+    ///
+    /// Returns `None` at version 3, or if the section doesn't end with a trailer,
+    /// which is usual since bootloaders add it when appending the bootconfig to the
+    /// ramdisk.
+    pub fn bootconfig_trailer<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+    ) -> Result<Option<BootconfigTrailer>, Error> {
+        let (Some(v4), Some(position)) = (&self.v4, self.bootconfig_position()) else {
+            return Ok(None);
+        };
+        let Some(offset) = (v4.bootconfig_size as usize).checked_sub(BootconfigTrailer::SIZE)
+        else {
+            return Ok(None);
+        };
+        reader.seek(SeekFrom::Start((position + offset) as u64))?;
+        let mut trailer = [0; BootconfigTrailer::SIZE];
+        reader.read_exact(&mut trailer)?;
+        Ok(BootconfigTrailer::parse(&trailer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use binrw::BinWrite;
+
+    use super::*;
+    use crate::{vendor::tests::vendor_header, VendorHeaderV4};
+
+    /// Builds a bootconfig blob with a trailer.
+    fn bootconfig(params: &[u8]) -> Vec<u8> {
+        let mut blob = params.to_vec();
+        blob.extend((params.len() as u32).to_le_bytes());
+        blob.extend(BootconfigTrailer::checksum_of(params).to_le_bytes());
+        blob.extend(BootconfigTrailer::MAGIC);
+        blob
+    }
+
+    #[test]
+    fn parse_trailer() {
+        let params = b"androidboot.hardware = qcom\n";
+        let blob = bootconfig(params);
+        let trailer = BootconfigTrailer::parse(&blob).unwrap();
+        assert_eq!(trailer.params_size, params.len() as u32);
+        assert_eq!(
+            trailer.checksum,
+            params.iter().map(|b| u32::from(*b)).sum::<u32>()
+        );
+        assert!(trailer.matches(params));
+        assert!(!trailer.matches(b"androidboot.hardware = qcoM\n"));
+
+        assert_eq!(BootconfigTrailer::parse(params), None);
+        assert_eq!(BootconfigTrailer::parse(&blob[..19]), None);
+    }
+
+    #[test]
+    fn vendor_bootconfig_trailer() {
+        let blob = bootconfig(b"androidboot.slot_suffix = _a\n");
+        let hdr = vendor_header(Some(VendorHeaderV4 {
+            bootconfig_size: blob.len() as u32,
+            ..Default::default()
+        }));
+        let mut image = Cursor::new(Vec::new());
+        hdr.write(&mut image).unwrap();
+        let position = hdr.bootconfig_position().unwrap();
+        image.get_mut().resize(position, 0);
+        image.get_mut().extend(&blob);
+
+        let trailer = hdr.bootconfig_trailer(&mut image).unwrap().unwrap();
+        assert!(trailer.matches(&blob[..blob.len() - BootconfigTrailer::SIZE]));
+
+        assert_eq!(
+            vendor_header(None).bootconfig_trailer(&mut image).unwrap(),
+            None
+        );
+    }
+}
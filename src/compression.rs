@@ -0,0 +1,254 @@
+use std::{
+    fmt,
+    io::{Read, Seek, SeekFrom},
+};
+
+use crate::{Error, Header};
+
+/// Compression format of a kernel or ramdisk
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Compression {
+    /// gzip
+    Gzip,
+    /// LZ4 frame format
+    Lz4Frame,
+    /// Legacy LZ4 format, as produced by `lz4 -l`
+    ///
+    /// Generic LZ4 frame decompressors can't read this.
+    Lz4Legacy,
+    /// xz
+    Xz,
+    /// Legacy LZMA, as produced by `lzma` or `xz --format=lzma`
+    ///
+    /// xz decompressors can't read this without a separate LZMA-alone mode.
+    LzmaAlone,
+    /// Zstandard
+    Zstd,
+    /// Unrecognized, possibly uncompressed
+    Unknown,
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Gzip => "gzip",
+            Self::Lz4Frame => "lz4",
+            Self::Lz4Legacy => "lz4-legacy",
+            Self::Xz => "xz",
+            Self::LzmaAlone => "lzma",
+            Self::Zstd => "zstd",
+            Self::Unknown => "unknown",
+        })
+    }
+}
+
+/// Detects the compression format of data from its leading bytes.
+#[must_use]
+pub fn detect_compression(bytes: &[u8]) -> Compression {
+    match bytes {
+        [0x1f, 0x8b, ..] => Compression::Gzip,
+        [0x04, 0x22, 0x4d, 0x18, ..] => Compression::Lz4Frame,
+        [0x02, 0x21, 0x4c, 0x18, ..] => Compression::Lz4Legacy,
+        [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => Compression::Xz,
+        // Properties for the default lc=3, lp=0, pb=2, then the low bytes of a
+        // dictionary size that's a multiple of 64 KiB, as usual
+        [0x5d, 0x00, 0x00, ..] => Compression::LzmaAlone,
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => Compression::Zstd,
+        _ => Compression::Unknown,
+    }
+}
+
+/// Format of a kernel
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum KernelFormat {
+    /// Uncompressed ARM64 `Image`
+    RawArm64Image,
+    /// Compressed kernel
+    Compressed(Compression),
+    /// Unrecognized
+    Unknown,
+}
+
+impl fmt::Display for KernelFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RawArm64Image => f.write_str("none (arm64 Image)"),
+            Self::Compressed(compression) => fmt::Display::fmt(compression, f),
+            Self::Unknown => f.write_str("unknown"),
+        }
+    }
+}
+
+/// Detects the format of a kernel from its leading bytes.
+///
+/// At least 64 bytes are needed to recognize an ARM64 `Image`.
+#[must_use]
+pub fn detect_kernel_format(bytes: &[u8]) -> KernelFormat {
+    if bytes.get(56..60) == Some(b"ARM\x64") {
+        return KernelFormat::RawArm64Image;
+    }
+    match detect_compression(bytes) {
+        Compression::Unknown => KernelFormat::Unknown,
+        compression => KernelFormat::Compressed(compression),
+    }
+}
+
+/// Architecture of an uncompressed or self-decompressing kernel
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum KernelArch {
+    /// ARM64 `Image`
+    Arm64,
+    /// 32-bit ARM `zImage`
+    Arm,
+    /// x86 `bzImage`
+    X86,
+    /// Unrecognized, e.g. compressed
+    Unknown,
+}
+
+impl fmt::Display for KernelArch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Arm64 => "arm64",
+            Self::Arm => "arm",
+            Self::X86 => "x86",
+            Self::Unknown => "unknown",
+        })
+    }
+}
+
+/// Detects the architecture of a kernel from the magic numbers in its header.
+///
+/// At least 0x206 bytes are needed to recognize an x86 `bzImage`.
+#[must_use]
+pub fn detect_kernel_arch(bytes: &[u8]) -> KernelArch {
+    if bytes.get(56..60) == Some(b"ARM\x64") {
+        KernelArch::Arm64
+    } else if bytes.get(0x24..0x28) == Some(&0x016f2818_u32.to_le_bytes()) {
+        KernelArch::Arm
+    } else if bytes.get(0x202..0x206) == Some(b"HdrS") {
+        KernelArch::X86
+    } else {
+        KernelArch::Unknown
+    }
+}
+
+impl Header {
+    /// Returns a reader of the decompressed ramdisk.
+    ///
+    /// zstd ramdisks are decompressed with the `zstd` feature. Ramdisks of
+    /// [unknown](Compression::Unknown) compression are read as-is, and other formats
+    /// fail with [`Error::UnsupportedCompression`].
+    pub fn ramdisk_reader<'a, R: Read + Seek + 'a>(
+        &self,
+        mut reader: R,
+    ) -> Result<Box<dyn Read + 'a>, Error> {
+        let position = self.ramdisk_position() as u64;
+        reader.seek(SeekFrom::Start(position))?;
+        let mut magic = Vec::with_capacity(8);
+        (&mut reader)
+            .take(8.min(self.ramdisk_size() as u64))
+            .read_to_end(&mut magic)?;
+        reader.seek(SeekFrom::Start(position))?;
+
+        let ramdisk = reader.take(self.ramdisk_size() as u64);
+        match detect_compression(&magic) {
+            Compression::Unknown => Ok(Box::new(ramdisk)),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(Box::new(zstd::Decoder::new(ramdisk)?)),
+            compression => Err(Error::UnsupportedCompression(compression)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect() {
+        assert_eq!(
+            detect_compression(&0x184D2204u32.to_le_bytes()),
+            Compression::Lz4Frame
+        );
+        assert_eq!(
+            detect_compression(&0x184C2102u32.to_le_bytes()),
+            Compression::Lz4Legacy
+        );
+        assert_eq!(detect_compression(&[0x1f, 0x8b, 8]), Compression::Gzip);
+        assert_eq!(detect_compression(b"070701"), Compression::Unknown);
+        assert_eq!(detect_compression(&[]), Compression::Unknown);
+        assert_eq!(
+            detect_compression(&0xFD2FB528u32.to_le_bytes()),
+            Compression::Zstd
+        );
+    }
+
+    #[test]
+    fn ramdisk_reader() {
+        use crate::{tests::header_v3, SectionKind};
+
+        let ramdisk = b"070701 cpio archive".repeat(100);
+        #[cfg(feature = "zstd")]
+        let stored = zstd::encode_all(&ramdisk[..], 3).unwrap();
+        #[cfg(not(feature = "zstd"))]
+        let stored = ramdisk.clone();
+
+        let hdr = Header::V3(header_v3(100, stored.len() as u32));
+        let mut image = crate::tests::build_image(&hdr);
+        let range = hdr.section_range(SectionKind::Ramdisk).unwrap();
+        image[range.start as usize..range.end as usize].copy_from_slice(&stored);
+
+        let mut out = Vec::new();
+        hdr.ramdisk_reader(std::io::Cursor::new(&image))
+            .unwrap()
+            .read_to_end(&mut out)
+            .unwrap();
+        assert_eq!(out, ramdisk);
+
+        image[range.start as usize..][..2].copy_from_slice(&[0x1f, 0x8b]);
+        assert!(matches!(
+            hdr.ramdisk_reader(std::io::Cursor::new(&image)),
+            Err(Error::UnsupportedCompression(Compression::Gzip))
+        ));
+    }
+
+    #[test]
+    fn kernel_format() {
+        let mut image = [0; 64];
+        image[56..60].copy_from_slice(&0x644d5241u32.to_le_bytes());
+        assert_eq!(detect_kernel_format(&image), KernelFormat::RawArm64Image);
+
+        let xz = b"\xfd7zXZ\0\0\x04\xe6\xd6\xb4\x46";
+        assert_eq!(detect_compression(xz), Compression::Xz);
+        let lzma = b"\x5d\0\0\x80\0\xff\xff\xff\xff\xff\xff\xff\xff";
+        assert_eq!(detect_compression(lzma), Compression::LzmaAlone);
+        assert_eq!(Compression::LzmaAlone.to_string(), "lzma");
+        assert_eq!(
+            detect_kernel_format(xz),
+            KernelFormat::Compressed(Compression::Xz)
+        );
+        assert_eq!(detect_kernel_format(&[0; 64]), KernelFormat::Unknown);
+    }
+
+    #[test]
+    fn kernel_arch() {
+        let mut arm64 = [0; 64];
+        arm64[56..60].copy_from_slice(b"ARM\x64");
+        assert_eq!(detect_kernel_arch(&arm64), KernelArch::Arm64);
+
+        let mut zimage = [0; 0x30];
+        zimage[0x24..0x28].copy_from_slice(&[0x18, 0x28, 0x6f, 0x01]);
+        assert_eq!(detect_kernel_arch(&zimage), KernelArch::Arm);
+
+        let mut bzimage = [0; 0x206];
+        bzimage[0x202..].copy_from_slice(b"HdrS");
+        assert_eq!(detect_kernel_arch(&bzimage), KernelArch::X86);
+        assert_eq!(detect_kernel_arch(&bzimage[..0x205]), KernelArch::Unknown);
+
+        assert_eq!(detect_kernel_arch(&[0x1f, 0x8b, 8]), KernelArch::Unknown);
+        assert_eq!(KernelArch::Arm64.to_string(), "arm64");
+    }
+}
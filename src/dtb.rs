@@ -0,0 +1,221 @@
+use std::{fmt, io::Cursor, ops::Range};
+
+use binrw::BinRead;
+
+use crate::Error;
+
+// Unlike the boot image header, FDT and DTBO headers are big-endian.
+
+/// Flattened device tree (FDT) header, i.e. the start of a DTB
+#[derive(BinRead, Clone, Debug, PartialEq, Eq, Hash)]
+#[br(big, magic = 0xd00dfeed_u32)]
+pub struct FdtHeader {
+    /// Size of the DTB, including this header
+    pub totalsize: u32,
+    /// Offset of the structure block
+    pub off_dt_struct: u32,
+    /// Offset of the strings block
+    pub off_dt_strings: u32,
+    /// Offset of the memory reservation block
+    pub off_mem_rsvmap: u32,
+    /// Version of the FDT format
+    pub version: u32,
+    /// Lowest version the DTB is backwards compatible with
+    pub last_comp_version: u32,
+    /// Physical ID of the boot CPU
+    pub boot_cpuid_phys: u32,
+    /// Size of the strings block
+    pub size_dt_strings: u32,
+    /// Size of the structure block
+    pub size_dt_struct: u32,
+}
+impl FdtHeader {
+    /// FDT magic, as stored in big-endian
+    pub const MAGIC: [u8; 4] = 0xd00dfeed_u32.to_be_bytes();
+
+    /// Parses an FDT header from the start of `bytes`.
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(Self::read(&mut Cursor::new(bytes))?)
+    }
+}
+
+/// Android DTBO image header, as used by the recovery DTBO section
+#[derive(BinRead, Clone, Debug, PartialEq, Eq, Hash)]
+#[br(big, magic = 0xd7b7ab1e_u32)]
+pub struct DtboHeader {
+    /// Size of the DTBO image, including this header
+    pub total_size: u32,
+    /// Size of this header
+    pub header_size: u32,
+    /// Size of each entry in the DT entry table
+    pub dt_entry_size: u32,
+    /// Number of entries in the DT entry table
+    pub dt_entry_count: u32,
+    /// Offset of the DT entry table
+    pub dt_entries_offset: u32,
+    /// Page size in bytes
+    pub page_size: u32,
+    /// Version of the DTBO format
+    pub version: u32,
+}
+impl DtboHeader {
+    /// DTBO magic, as stored in big-endian
+    pub const MAGIC: [u8; 4] = 0xd7b7ab1e_u32.to_be_bytes();
+
+    /// Parses a DTBO header from the start of `bytes`.
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(Self::read(&mut Cursor::new(bytes))?)
+    }
+}
+
+/// Contents of the recovery DTBO/ACPIO section of a v1 or v2 header
+///
+/// See [`HeaderV0::recovery_overlay_kind`](crate::HeaderV0::recovery_overlay_kind).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum OverlayKind {
+    /// Device tree overlays in a DTBO image, as used on ARM
+    Dtbo,
+    /// ACPI overlays in a cpio archive, as used on x86
+    Acpio,
+    /// Unrecognized, empty or absent
+    Unknown,
+}
+impl OverlayKind {
+    /// Detects the kind of a recovery overlay from its leading bytes.
+    #[must_use]
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(&DtboHeader::MAGIC) {
+            Self::Dtbo
+        } else if bytes.starts_with(b"07070") {
+            // "070701"/"070702" (newc) and "070707" (odc) cpio
+            Self::Acpio
+        } else {
+            Self::Unknown
+        }
+    }
+}
+impl fmt::Display for OverlayKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Dtbo => "dtbo",
+            Self::Acpio => "acpio",
+            Self::Unknown => "unknown",
+        })
+    }
+}
+
+/// Splits concatenated DTBs, such as a v2 boot image's DTB section, into individual
+/// blobs.
+///
+/// Zero padding after the last DTB is ignored.
+pub fn split_fdts(mut bytes: &[u8]) -> Result<Vec<&[u8]>, Error> {
+    let mut fdts = Vec::new();
+    let mut offset = 0;
+    while bytes.iter().any(|b| *b != 0) {
+        let totalsize = FdtHeader::parse(bytes)
+            .map_err(|_| Error::InvalidFdt { offset })?
+            .totalsize as usize;
+        if totalsize < 40 || totalsize > bytes.len() {
+            return Err(Error::InvalidFdt { offset });
+        }
+        let (fdt, rest) = bytes.split_at(totalsize);
+        fdts.push(fdt);
+        bytes = rest;
+        offset += totalsize;
+    }
+    Ok(fdts)
+}
+
+/// Finds DTBs appended to a kernel, as done for ARM zImages whose bootloader doesn't
+/// pass a device tree, and returns their range in `kernel`.
+///
+/// The range starts at the first FDT magic that is followed only by valid DTBs and
+/// zero padding, which is excluded. Returns `None` if there is no such DTB.
+#[must_use]
+pub fn find_appended_dtb(kernel: &[u8]) -> Option<Range<usize>> {
+    let mut start = 0;
+    while let Some(pos) = kernel[start..]
+        .windows(FdtHeader::MAGIC.len())
+        .position(|w| w == FdtHeader::MAGIC)
+    {
+        let offset = start + pos;
+        if let Ok(fdts) = split_fdts(&kernel[offset..]) {
+            let len: usize = fdts.iter().map(|fdt| fdt.len()).sum();
+            return Some(offset..offset + len);
+        }
+        start = offset + 1;
+    }
+    None
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Builds an FDT header of `totalsize` bytes followed by zeroes.
+    pub(crate) fn fdt(totalsize: u32) -> Vec<u8> {
+        let mut dtb = FdtHeader::MAGIC.to_vec();
+        for field in [totalsize, 40, 40, 40, 17, 16, 0, 0, 0] {
+            dtb.extend(field.to_be_bytes());
+        }
+        dtb.resize(totalsize as usize, 0);
+        dtb
+    }
+
+    #[test]
+    fn fdt_big_endian() {
+        let hdr = FdtHeader::parse(&fdt(0x100)).unwrap();
+        assert_eq!(hdr.totalsize, 0x100);
+        assert_eq!(hdr.version, 17);
+
+        let mut little = fdt(0x100);
+        little[..4].copy_from_slice(&0xd00dfeed_u32.to_le_bytes());
+        assert!(FdtHeader::parse(&little).is_err());
+    }
+
+    #[test]
+    fn split() {
+        let mut dtbs = fdt(0x100);
+        dtbs.extend(fdt(0x80));
+        dtbs.extend([0; 16]);
+        let fdts = split_fdts(&dtbs).unwrap();
+        assert_eq!(fdts, [&dtbs[..0x100], &dtbs[0x100..0x180]]);
+
+        assert!(split_fdts(&[]).unwrap().is_empty());
+        assert!(matches!(
+            split_fdts(&dtbs[..0x170]),
+            Err(Error::InvalidFdt { offset: 0x100 })
+        ));
+    }
+
+    #[test]
+    fn appended_dtb() {
+        let mut zimage = vec![0; 0x24];
+        zimage.extend(0x016f2818_u32.to_le_bytes());
+        zimage.resize(0x1000, 0xaa);
+        // A stray magic in the compressed data
+        zimage[0x800..0x804].copy_from_slice(&FdtHeader::MAGIC);
+        assert_eq!(find_appended_dtb(&zimage), None);
+
+        zimage.extend(fdt(0x100));
+        zimage.extend(fdt(0x80));
+        zimage.extend([0; 16]);
+        assert_eq!(find_appended_dtb(&zimage), Some(0x1000..0x1180));
+    }
+
+    #[test]
+    fn dtbo_big_endian() {
+        let mut dtbo = DtboHeader::MAGIC.to_vec();
+        for field in [64u32, 32, 32, 1, 32, 2048, 0] {
+            dtbo.extend(field.to_be_bytes());
+        }
+        let hdr = DtboHeader::parse(&dtbo).unwrap();
+        assert_eq!(hdr.total_size, 64);
+        assert_eq!(hdr.dt_entry_count, 1);
+        assert_eq!(hdr.page_size, 2048);
+
+        dtbo[..4].copy_from_slice(&0xd7b7ab1e_u32.to_le_bytes());
+        assert!(DtboHeader::parse(&dtbo).is_err());
+    }
+}
@@ -0,0 +1,458 @@
+use std::{fmt, io};
+
+use crate::{Compression, ForeignFormat, HeaderField, OsPatch, OsVersion, SectionKind};
+
+/// Error type for boot image operations
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// An I/O error occurred.
+    Io(io::Error),
+    /// Reading or writing a header failed.
+    Binrw(binrw::Error),
+    /// The file isn't an Android boot image, but another recognized format.
+    ForeignFormat(ForeignFormat),
+    /// The magic is close to `ANDROID!`, but not equal, as in a partially corrupted
+    /// image.
+    CorruptedMagic {
+        /// Number of bytes matching `ANDROID!`, see
+        /// [`magic_similarity`](crate::magic_similarity)
+        matching: u8,
+    },
+    /// The image is shorter than its header.
+    TooSmall {
+        /// Length of the header
+        need: u64,
+        /// Length of the image
+        have: u64,
+    },
+    /// The header version is unknown.
+    UnknownHeaderVersion {
+        /// Header version
+        version: u32,
+    },
+    /// The header size field doesn't match the header version.
+    HeaderSizeMismatch {
+        /// Header version
+        version: u32,
+        /// Header size of the version
+        expected: u32,
+        /// Header size field
+        actual: u32,
+    },
+    /// The operation isn't supported for the header version.
+    UnsupportedHeaderVersion {
+        /// Header version
+        version: u32,
+    },
+    /// Downgrading a header would drop non-zero fields.
+    LossyDowngrade {
+        /// Target header version
+        version: u32,
+    },
+    /// A size doesn't fit in 32 bits after converting it to bytes.
+    SizeOverflow,
+    /// The v4 boot signature size is zero.
+    EmptySignature,
+    /// The v4 boot signature size is larger than [`HeaderV3::MAX_SIGNATURE_SIZE`].
+    ///
+    /// [`HeaderV3::MAX_SIGNATURE_SIZE`]: crate::HeaderV3::MAX_SIGNATURE_SIZE
+    SignatureTooLarge {
+        /// Declared signature size, or the length of the signature data
+        size: u64,
+    },
+    /// The v4 boot signature extends past the end of the image.
+    SignatureOutOfBounds {
+        /// End offset of the signature
+        end: u64,
+        /// Length of the image
+        image_len: u64,
+    },
+    /// A v4 boot signature extends past the original image or into the vbmeta image
+    /// recorded by the image's AVB footer.
+    AvbFooterMismatch {
+        /// End offset of the signature
+        signature_end: u64,
+        /// Original image size recorded by the footer
+        original_image_size: u64,
+        /// Vbmeta offset recorded by the footer
+        vbmeta_offset: u64,
+    },
+    /// The vbmeta image recorded by an AVB footer extends past the footer.
+    VbmetaOutOfBounds {
+        /// End offset of the vbmeta image
+        end: u64,
+        /// Offset of the footer
+        footer_position: u64,
+    },
+    /// A byte string doesn't fit in its header field.
+    FieldTooLong {
+        /// Length of the byte string
+        len: usize,
+        /// Size of the field
+        max: usize,
+    },
+    /// A kernel command line doesn't fit in the header.
+    CmdlineTooLong(CmdlineTooLong),
+    /// The kernel command line fills its field without a null terminator.
+    UnterminatedCmdline,
+    /// An OS version doesn't fit in 21 bits.
+    InvalidOsVersion(OsVersion),
+    /// An OS patch level has a month over 12 or a year outside 2000-2127.
+    InvalidOsPatch(OsPatch),
+    /// A page size isn't a power of two, or the header's version has a fixed page
+    /// size.
+    InvalidPageSize {
+        /// Page size in bytes
+        page_size: u32,
+    },
+    /// Decompressing the compression format isn't supported, or its feature isn't
+    /// enabled.
+    UnsupportedCompression(Compression),
+    /// A DTB has a bad magic or size.
+    InvalidFdt {
+        /// Offset of the DTB in the DTB section
+        offset: usize,
+    },
+    /// The vendor ramdisk table's entries don't exactly fill its declared size.
+    TableSizeMismatch {
+        /// Declared table size
+        expected: u32,
+        /// Entry count times entry size
+        computed: u64,
+    },
+    /// The vendor ramdisk table has more entries than
+    /// [`VendorHeader::MAX_RAMDISK_COUNT`].
+    ///
+    /// [`VendorHeader::MAX_RAMDISK_COUNT`]: crate::VendorHeader::MAX_RAMDISK_COUNT
+    TooManyRamdisks {
+        /// Declared entry count
+        count: u32,
+    },
+    /// The vendor ramdisk table's entry size is smaller than
+    /// [`VendorRamdiskTableEntry::SIZE`].
+    ///
+    /// [`VendorRamdiskTableEntry::SIZE`]: crate::VendorRamdiskTableEntry::SIZE
+    InvalidRamdiskTableEntrySize {
+        /// Declared entry size
+        size: u32,
+    },
+    /// A vendor ramdisk table entry extends past the vendor ramdisk.
+    RamdiskFragmentOutOfRange {
+        /// Offset of the fragment in the vendor ramdisk
+        offset: u32,
+        /// Size of the fragment
+        size: u32,
+        /// Size of the vendor ramdisk
+        vendor_ramdisk_size: u32,
+    },
+    /// The file isn't an OTA `payload.bin`.
+    NotOtaPayload,
+    /// An OTA payload's manifest is malformed.
+    InvalidOtaManifest,
+    /// The header was written twice, or not written before finishing the image.
+    HeaderOutOfOrder,
+    /// A section was written before the header, after a later section or twice.
+    SectionOutOfOrder {
+        /// Kind of the section
+        kind: SectionKind,
+    },
+    /// A section was supplied for a header version that doesn't have it, e.g. a
+    /// second stage bootloader for version 3.
+    SectionNotInVersion {
+        /// Kind of the section
+        kind: SectionKind,
+        /// Header version
+        version: u32,
+    },
+    /// A header field was patched for a header version that doesn't have it.
+    FieldNotInVersion {
+        /// The field
+        field: HeaderField,
+        /// Header version
+        version: u32,
+    },
+    /// A section is larger than the allowed maximum.
+    SectionTooLarge {
+        /// Kind of the section
+        kind: SectionKind,
+        /// Size declared in the header
+        size: u32,
+        /// Maximum size
+        max: u64,
+    },
+    /// A non-empty section declared in the header was not written.
+    MissingSection {
+        /// Kind of the section
+        kind: SectionKind,
+    },
+    /// Section data doesn't match the size declared in the header.
+    SectionSizeMismatch {
+        /// Kind of the section
+        kind: SectionKind,
+        /// Size declared in the header
+        expected: u32,
+        /// Length of the data
+        actual: usize,
+    },
+    /// The image ends before a section does.
+    TruncatedSection {
+        /// Kind of the section
+        kind: SectionKind,
+        /// Size declared in the header
+        size: u32,
+        /// Bytes of the section in the image
+        available: u64,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "I/O error: {err}"),
+            Self::Binrw(err) => match err.root_cause() {
+                err if err.is_eof() => write!(f, "unexpected end of file"),
+                binrw::Error::AssertFail { message, .. } => f.write_str(message),
+                err => write!(f, "{err}"),
+            },
+            Self::ForeignFormat(format) => write!(f, "{format}"),
+            Self::CorruptedMagic { matching } => write!(
+                f,
+                "magic looks corrupted ({matching}/8 bytes match ANDROID!)"
+            ),
+            Self::TooSmall { need, have } => {
+                write!(
+                    f,
+                    "image is {have} bytes, but the header needs {need} bytes"
+                )
+            }
+            Self::UnknownHeaderVersion { version } => {
+                write!(f, "unknown header version {version}")
+            }
+            Self::HeaderSizeMismatch {
+                version,
+                expected,
+                actual,
+            } => {
+                write!(
+                    f,
+                    "header size is {actual}, but header version {version} has {expected}"
+                )?;
+                match version_with_header_size(*actual) {
+                    Some(likely) => write!(f, " (the size of version {likely})"),
+                    None => Ok(()),
+                }
+            }
+            Self::UnsupportedHeaderVersion { version } => {
+                write!(f, "operation isn't supported for header version {version}")
+            }
+            Self::LossyDowngrade { version } => write!(
+                f,
+                "downgrading to header version {version} would drop non-zero fields"
+            ),
+            Self::SizeOverflow => write!(f, "size in bytes doesn't fit in 32 bits"),
+            Self::EmptySignature => write!(f, "boot signature size is zero"),
+            Self::SignatureTooLarge { size } => write!(
+                f,
+                "boot signature size {size} exceeds the maximum of {}",
+                crate::HeaderV3::MAX_SIGNATURE_SIZE
+            ),
+            Self::SignatureOutOfBounds { end, image_len } => write!(
+                f,
+                "boot signature ends at {end}, past the end of the image ({image_len} bytes)"
+            ),
+            Self::AvbFooterMismatch {
+                signature_end,
+                original_image_size,
+                vbmeta_offset,
+            } => write!(
+                f,
+                "boot signature ends at {signature_end}, but the AVB footer records an original image size of {original_image_size} and vbmeta at {vbmeta_offset}"
+            ),
+            Self::VbmetaOutOfBounds {
+                end,
+                footer_position,
+            } => write!(
+                f,
+                "AVB footer records vbmeta ending at {end}, past the footer at {footer_position}"
+            ),
+            Self::FieldTooLong { len, max } => {
+                write!(f, "field is {len} bytes, but at most {max} bytes fit")
+            }
+            Self::CmdlineTooLong(err) => write!(f, "{err}"),
+            Self::UnterminatedCmdline => write!(f, "command line isn't null-terminated"),
+            Self::InvalidOsVersion(version) => write!(f, "invalid OS version {version}"),
+            Self::InvalidOsPatch(patch) => write!(
+                f,
+                "invalid OS patch level {}-{:02}",
+                patch.year(),
+                patch.month()
+            ),
+            Self::InvalidPageSize { page_size } => write!(f, "invalid page size {page_size}"),
+            Self::UnsupportedCompression(compression) => {
+                write!(f, "unsupported compression format {compression}")
+            }
+            Self::InvalidFdt { offset } => write!(f, "invalid DTB at offset {offset}"),
+            Self::TableSizeMismatch { expected, computed } => write!(
+                f,
+                "vendor ramdisk table entries take {computed} bytes, but the table is {expected} bytes"
+            ),
+            Self::TooManyRamdisks { count } => write!(
+                f,
+                "vendor ramdisk table has {count} entries, more than the maximum of {}",
+                crate::VendorHeader::MAX_RAMDISK_COUNT
+            ),
+            Self::InvalidRamdiskTableEntrySize { size } => {
+                write!(f, "invalid vendor ramdisk table entry size {size}")
+            }
+            Self::RamdiskFragmentOutOfRange {
+                offset,
+                size,
+                vendor_ramdisk_size,
+            } => write!(
+                f,
+                "vendor ramdisk fragment of {size} bytes at offset {offset} doesn't fit in the {vendor_ramdisk_size}-byte vendor ramdisk"
+            ),
+            Self::NotOtaPayload => write!(f, "not an OTA payload, expected magic `CrAU`"),
+            Self::InvalidOtaManifest => write!(f, "malformed OTA payload manifest"),
+            Self::HeaderOutOfOrder => write!(f, "header must be written once, first"),
+            Self::SectionOutOfOrder { kind } => write!(f, "{kind} written out of order"),
+            Self::SectionNotInVersion { kind, version } => {
+                write!(f, "header version {version} has no {kind} section")
+            }
+            Self::FieldNotInVersion { field, version } => {
+                write!(f, "header version {version} has no {field} field")
+            }
+            Self::SectionTooLarge { kind, size, max } => {
+                write!(f, "{kind} size {size} exceeds the maximum of {max}")
+            }
+            Self::MissingSection { kind } => write!(f, "{kind} was not written"),
+            Self::SectionSizeMismatch {
+                kind,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{kind} is {actual} bytes, but the header declares {expected} bytes"
+            ),
+            Self::TruncatedSection {
+                kind,
+                size,
+                available,
+            } => write!(
+                f,
+                "{kind} is truncated: the image has {available} of its {size} bytes"
+            ),
+        }
+    }
+}
+
+/// Returns the header version with a header size, to hint at the actual version of a
+/// header with a mismatched size.
+fn version_with_header_size(size: u32) -> Option<u32> {
+    match size {
+        1632 => Some(0),
+        1648 => Some(1),
+        1660 => Some(2),
+        1580 => Some(3),
+        1584 => Some(4),
+        _ => None,
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Binrw(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<CmdlineTooLong> for Error {
+    fn from(err: CmdlineTooLong) -> Self {
+        Self::CmdlineTooLong(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<binrw::Error> for Error {
+    fn from(err: binrw::Error) -> Self {
+        Self::Binrw(err)
+    }
+}
+
+/// Error for a kernel command line that doesn't fit in the header
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CmdlineTooLong {
+    /// Length of the command line
+    pub len: usize,
+    /// Maximum length of the command line, excluding the null terminator
+    pub max: usize,
+}
+
+impl fmt::Display for CmdlineTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "command line is {} bytes, but at most {} bytes fit",
+            self.len, self.max
+        )
+    }
+}
+
+impl std::error::Error for CmdlineTooLong {}
+
+/// Error for a boot and vendor boot image that don't belong together
+///
+/// See [`validate_pair`](crate::validate_pair).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PairError {
+    /// The boot image header is older than version 3, so it doesn't take a vendor
+    /// boot image.
+    BootVersion {
+        /// Boot image header version
+        version: u32,
+    },
+}
+
+impl fmt::Display for PairError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BootVersion { version } => write!(
+                f,
+                "boot image header version {version} doesn't use a vendor boot image"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PairError {}
+
+/// Error for a malformed OS version or patch level string
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ParseOsVersionError {
+    /// The malformed string
+    pub input: String,
+    /// Description of the expected format
+    pub expected: &'static str,
+}
+
+impl fmt::Display for ParseOsVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid value `{}`, expected {}",
+            self.input, self.expected
+        )
+    }
+}
+
+impl std::error::Error for ParseOsVersionError {}
@@ -0,0 +1,338 @@
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use crate::{read_trailing_tag, Error, Header, Section, SectionKind, TrailingTag};
+
+/// Sections of a boot image, read into memory
+///
+/// See [`Header::extract_all`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ExtractedImage {
+    /// Kernel, empty if the kernel size is zero
+    pub kernel: Vec<u8>,
+    /// Ramdisk, empty if the ramdisk size is zero
+    pub ramdisk: Vec<u8>,
+    /// Second stage bootloader (v0-v2), `None` if absent or empty
+    pub second: Option<Vec<u8>>,
+    /// Recovery DTBO/ACPIO (v1-v2), `None` if absent or empty
+    pub recovery_dtbo: Option<Vec<u8>>,
+    /// DTB (v2), `None` if absent or empty
+    pub dtb: Option<Vec<u8>>,
+    /// Boot signature (v4), `None` if absent or empty
+    pub boot_signature: Option<Vec<u8>>,
+    /// Vendor tag after the sections, appended again by
+    /// [`write_image`](crate::write_image). Set it to `None` to drop the tag.
+    pub trailing_tag: Option<TrailingTag>,
+}
+impl ExtractedImage {
+    /// Returns a section's data, empty if it's absent.
+    #[must_use]
+    pub fn section(&self, kind: SectionKind) -> &[u8] {
+        match kind {
+            SectionKind::Kernel => &self.kernel,
+            SectionKind::Ramdisk => &self.ramdisk,
+            SectionKind::Second => self.second.as_deref().unwrap_or_default(),
+            SectionKind::RecoveryDtbo => self.recovery_dtbo.as_deref().unwrap_or_default(),
+            SectionKind::Dtb => self.dtb.as_deref().unwrap_or_default(),
+            SectionKind::BootSignature => self.boot_signature.as_deref().unwrap_or_default(),
+        }
+    }
+}
+
+impl Header {
+    /// Reads every section of the boot image into memory.
+    ///
+    /// The kernel and ramdisk are always returned, while the other sections are
+    /// `None` if the header's version doesn't have them or they're empty. A known
+    /// vendor tag after the sections is kept in
+    /// [`trailing_tag`](ExtractedImage::trailing_tag).
+    pub fn extract_all<R: Read + Seek>(&self, reader: &mut R) -> Result<ExtractedImage, Error> {
+        let mut image = ExtractedImage::default();
+        for section in self.sections() {
+            let data = read_section(reader, section)?;
+            let slot = match section.kind {
+                SectionKind::Kernel => {
+                    image.kernel = data;
+                    continue;
+                }
+                SectionKind::Ramdisk => {
+                    image.ramdisk = data;
+                    continue;
+                }
+                SectionKind::Second => &mut image.second,
+                SectionKind::RecoveryDtbo => &mut image.recovery_dtbo,
+                SectionKind::Dtb => &mut image.dtb,
+                SectionKind::BootSignature => &mut image.boot_signature,
+            };
+            if !data.is_empty() {
+                *slot = Some(data);
+            }
+        }
+        image.trailing_tag = read_trailing_tag(reader, self.total_size())?;
+        Ok(image)
+    }
+
+    /// Copies a section to `writer` without reading it into memory, returning the
+    /// number of bytes written.
+    ///
+    /// This is the streaming counterpart of [`extract_all`](Self::extract_all).
+    /// Nothing is written if the header's version doesn't have the section. Fails
+    /// with [`Error::TruncatedSection`] if the reader ends before the section does.
+    pub fn read_section_into<R: Read + Seek, W: Write>(
+        &self,
+        reader: &mut R,
+        kind: SectionKind,
+        writer: &mut W,
+    ) -> Result<u64, Error> {
+        let Some(section) = self.section(kind) else {
+            return Ok(0);
+        };
+        reader.seek(SeekFrom::Start(section.position as u64))?;
+        let copied = io::copy(&mut reader.take(section.size as u64), writer)?;
+        check_truncated(section, copied)?;
+        Ok(copied)
+    }
+
+    /// Copies a section followed by its padding up to the page size to `writer`,
+    /// returning the number of bytes written.
+    ///
+    /// The padding is copied from the image, where it's normally zeroes. If the
+    /// image ends within the padding, the rest is filled with zeroes. Nothing is
+    /// written if the header's version doesn't have the section. Fails with
+    /// [`Error::TruncatedSection`] if the image ends within the section.
+    pub fn extract_section_padded<R: Read + Seek, W: Write>(
+        &self,
+        reader: &mut R,
+        kind: SectionKind,
+        writer: &mut W,
+    ) -> Result<u64, Error> {
+        let Some(section) = self.section(kind) else {
+            return Ok(0);
+        };
+        let size = u64::from(section.size);
+        let padded = size + self.trailing_padding(kind) as u64;
+        reader.seek(SeekFrom::Start(section.position as u64))?;
+        let copied = io::copy(&mut reader.take(padded), writer)?;
+        check_truncated(section, copied.min(size))?;
+        io::copy(&mut io::repeat(0).take(padded - copied), writer)?;
+        Ok(padded)
+    }
+}
+
+/// Reads a section, failing if the reader ends before it does.
+pub(crate) fn read_section<R: Read + Seek>(
+    reader: &mut R,
+    section: Section,
+) -> Result<Vec<u8>, Error> {
+    reader.seek(SeekFrom::Start(section.position as u64))?;
+    let mut data = Vec::new();
+    reader.take(section.size as u64).read_to_end(&mut data)?;
+    check_truncated(section, data.len() as u64)?;
+    Ok(data)
+}
+
+/// Fails with [`Error::TruncatedSection`] if fewer than the section's bytes were
+/// read.
+fn check_truncated(section: Section, available: u64) -> Result<(), Error> {
+    if available < section.size as u64 {
+        return Err(Error::TruncatedSection {
+            kind: section.kind,
+            size: section.size,
+            available,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        tests::{build_image, header_v0},
+        HeaderV0Versioned,
+    };
+
+    #[test]
+    fn extract_all_v2() {
+        let mut hdr = header_v0(HeaderV0Versioned::V2 {
+            recovery_dtbo_size: 0,
+            recovery_dtbo_addr: 0,
+            dtb_size: 300,
+            dtb_addr: 0x11f00000,
+        });
+        hdr.kernel_size = 5000;
+        hdr.ramdisk_size = 100;
+        let hdr = Header::V0(hdr);
+        let image = build_image(&hdr);
+
+        let extracted = hdr.extract_all(&mut Cursor::new(&image)).unwrap();
+        assert_eq!(extracted.kernel, vec![SectionKind::Kernel as u8 + 1; 5000]);
+        assert_eq!(extracted.ramdisk, vec![SectionKind::Ramdisk as u8 + 1; 100]);
+        assert_eq!(extracted.second, None);
+        assert_eq!(extracted.recovery_dtbo, None);
+        assert_eq!(extracted.dtb, Some(vec![SectionKind::Dtb as u8 + 1; 300]));
+        assert_eq!(extracted.boot_signature, None);
+
+        assert!(hdr
+            .extract_all(&mut Cursor::new(&image[..image.len() - 2048]))
+            .is_err());
+    }
+
+    /// Builds a v1 or v2 image byte by byte, independently of the writer, with a
+    /// 2048-byte page, a 3000-byte kernel, a 100-byte ramdisk, a 500-byte second stage
+    /// bootloader and, for v2, a 300-byte DTB.
+    fn raw_image(version: u32) -> Vec<u8> {
+        let mut image = b"ANDROID!".to_vec();
+        for field in [3000u32, 0, 100, 0, 500, 0, 0, 2048, version] {
+            image.extend(field.to_le_bytes());
+        }
+        image.resize(1644, 0);
+        let (header_size, end) = if version == 1 {
+            (1648u32, 12288)
+        } else {
+            (1660, 14336)
+        };
+        image.extend(header_size.to_le_bytes());
+        if version == 2 {
+            image.extend(300u32.to_le_bytes());
+        }
+        image.resize(end, 0);
+        image[2048..][..3000].fill(1);
+        image[6144..][..100].fill(2);
+        image[8192..][..500].fill(3);
+        if version == 2 {
+            image[10240..][..300].fill(5);
+        }
+        image
+    }
+
+    #[test]
+    fn extract_second_v1_v2() {
+        for version in [1, 2] {
+            let image = raw_image(version);
+            let hdr = Header::parse(&mut Cursor::new(&image)).unwrap();
+            assert_eq!(hdr.header_len(), [1648, 1660][version as usize - 1]);
+            let Header::V0(v0) = &hdr else { unreachable!() };
+            assert_eq!(v0.second_bootloader_position(), 8192);
+            assert_eq!(v0.recovery_dtbo_position(), Some(10240));
+            assert_eq!(v0.dtb_position(), (version == 2).then_some(10240));
+
+            let extracted = hdr.extract_all(&mut Cursor::new(&image)).unwrap();
+            assert_eq!(extracted.kernel, [1; 3000]);
+            assert_eq!(extracted.ramdisk, [2; 100]);
+            assert_eq!(extracted.second, Some(vec![3; 500]));
+            assert_eq!(extracted.recovery_dtbo, None);
+            assert_eq!(extracted.dtb, (version == 2).then(|| vec![5; 300]));
+        }
+    }
+
+    #[test]
+    fn read_section_into() {
+        let image = raw_image(2);
+        let hdr = Header::parse(&mut Cursor::new(&image)).unwrap();
+        let mut kernel = Vec::new();
+        let written = hdr
+            .read_section_into(&mut Cursor::new(&image), SectionKind::Kernel, &mut kernel)
+            .unwrap();
+        assert_eq!(written, 3000);
+        assert_eq!(kernel, image[2048..][..3000]);
+
+        let mut dtb = Vec::new();
+        assert!(matches!(
+            hdr.read_section_into(
+                &mut Cursor::new(&image[..10400]),
+                SectionKind::Dtb,
+                &mut dtb
+            ),
+            Err(Error::TruncatedSection {
+                kind: SectionKind::Dtb,
+                size: 300,
+                available: 160
+            })
+        ));
+    }
+
+    #[test]
+    fn extract_zero_kernel() {
+        let mut hdr = header_v0(HeaderV0Versioned::V0);
+        hdr.ramdisk_size = 100;
+        let hdr = Header::V0(hdr);
+        assert_eq!(hdr.kernel_position(), 2048);
+        assert_eq!(hdr.ramdisk_position(), hdr.kernel_position());
+        let image = build_image(&hdr);
+        assert_eq!(image.len(), 4096);
+
+        let extracted = hdr.extract_all(&mut Cursor::new(&image)).unwrap();
+        assert!(extracted.kernel.is_empty());
+        assert_eq!(extracted.ramdisk, vec![SectionKind::Ramdisk as u8 + 1; 100]);
+    }
+
+    #[test]
+    fn extract_section_padded() {
+        let mut hdr = header_v0(HeaderV0Versioned::V0);
+        hdr.kernel_size = 3000;
+        hdr.ramdisk_size = 100;
+        let hdr = Header::V0(hdr);
+        let image = build_image(&hdr);
+
+        let mut padded = Vec::new();
+        let len = hdr
+            .extract_section_padded(&mut Cursor::new(&image), SectionKind::Kernel, &mut padded)
+            .unwrap();
+        assert_eq!(len, 4096);
+        assert_eq!(padded.len(), 4096);
+        let unpadded = hdr.extract_all(&mut Cursor::new(&image)).unwrap().kernel;
+        assert_eq!(unpadded.len(), 3000);
+        assert_eq!(padded[..3000], unpadded);
+        assert_eq!(padded[3000..], [0; 1096]);
+
+        // The image ends without padding the ramdisk
+        let mut padded = Vec::new();
+        let end = hdr.section_range(SectionKind::Ramdisk).unwrap().end as usize;
+        hdr.extract_section_padded(
+            &mut Cursor::new(&image[..end]),
+            SectionKind::Ramdisk,
+            &mut padded,
+        )
+        .unwrap();
+        assert_eq!(padded.len(), 2048);
+        assert!(matches!(
+            hdr.extract_section_padded(
+                &mut Cursor::new(&image[..end - 1]),
+                SectionKind::Ramdisk,
+                &mut Vec::new()
+            ),
+            Err(Error::TruncatedSection {
+                kind: SectionKind::Ramdisk,
+                size: 100,
+                available: 99
+            })
+        ));
+    }
+
+    #[test]
+    fn truncated_ramdisk() {
+        let mut hdr = header_v0(HeaderV0Versioned::V0);
+        hdr.kernel_size = 3000;
+        hdr.ramdisk_size = 100;
+        let hdr = Header::V0(hdr);
+        let image = build_image(&hdr);
+        let ramdisk = hdr.section(SectionKind::Ramdisk).unwrap().position;
+        let image = &image[..ramdisk + 40];
+
+        let err = hdr.extract_all(&mut Cursor::new(image)).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::TruncatedSection {
+                kind: SectionKind::Ramdisk,
+                size: 100,
+                available: 40
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "ramdisk is truncated: the image has 40 of its 100 bytes"
+        );
+    }
+}
@@ -0,0 +1,186 @@
+//! C-compatible representation of boot image headers v0-v2
+//!
+//! Build the crate as a `staticlib` or `cdylib` with the `ffi` feature to link it from
+//! C or C++. The manifest only builds an `rlib`, so pass the crate type to
+//! `cargo rustc`:
+//!
+//! ```sh
+//! cargo rustc --release --lib --features ffi --crate-type cdylib
+//! ```
+
+use std::io::Cursor;
+
+use crate::{Error, Header, HeaderV0, HeaderV0Versioned, OsVersionPatch};
+
+/// Flat, C-compatible boot image header for versions 0-2
+///
+/// Fields absent in the header's version are zero.
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CHeaderV0 {
+    /// Header version
+    pub header_version: u32,
+    /// Kernel size
+    pub kernel_size: u32,
+    /// Kernel physical load address
+    pub kernel_addr: u32,
+    /// Ramdisk size
+    pub ramdisk_size: u32,
+    /// Ramdisk physical load address
+    pub ramdisk_addr: u32,
+    /// Second bootloader size
+    pub second_bootloader_size: u32,
+    /// Second bootloader physical load address
+    pub second_bootloader_addr: u32,
+    /// Kernel tags physical load address
+    pub tags_addr: u32,
+    /// Page size in bytes
+    pub page_size: u32,
+    /// OS version and patch level
+    pub osversionpatch: u32,
+    /// Board or product name
+    pub board_name: [u8; 16],
+    /// Kernel command line, part 1
+    pub cmdline_part_1: [u8; 512],
+    /// Hash digest
+    pub hash_digest: [u8; 32],
+    /// Kernel command line, part 2
+    pub cmdline_part_2: [u8; 1024],
+    /// Recovery DTBO/ACPIO size (v1+)
+    pub recovery_dtbo_size: u32,
+    /// Recovery DTBO/ACPIO physical load address (v1+)
+    pub recovery_dtbo_addr: u64,
+    /// DTB size (v2)
+    pub dtb_size: u32,
+    /// DTB physical load address (v2)
+    pub dtb_addr: u64,
+}
+
+impl HeaderV0 {
+    /// Converts the header to its C representation.
+    #[must_use]
+    pub fn to_c(&self) -> CHeaderV0 {
+        let (recovery_dtbo_size, recovery_dtbo_addr, dtb_size, dtb_addr) = self.versioned.fields();
+        CHeaderV0 {
+            header_version: self.header_version(),
+            kernel_size: self.kernel_size,
+            kernel_addr: self.kernel_addr,
+            ramdisk_size: self.ramdisk_size,
+            ramdisk_addr: self.ramdisk_addr,
+            second_bootloader_size: self.second_bootloader_size,
+            second_bootloader_addr: self.second_bootloader_addr,
+            tags_addr: self.tags_addr,
+            page_size: self.page_size,
+            osversionpatch: self.osversionpatch.0,
+            board_name: self.board_name,
+            cmdline_part_1: *self.cmdline_part_1,
+            hash_digest: self.hash_digest,
+            cmdline_part_2: *self.cmdline_part_2,
+            recovery_dtbo_size,
+            recovery_dtbo_addr,
+            dtb_size,
+            dtb_addr,
+        }
+    }
+    /// Converts a header from its C representation.
+    ///
+    /// Fields absent in the header's version are ignored.
+    pub fn from_c(hdr: &CHeaderV0) -> Result<Self, Error> {
+        let versioned = match hdr.header_version {
+            0 => HeaderV0Versioned::V0,
+            1 => HeaderV0Versioned::V1 {
+                recovery_dtbo_size: hdr.recovery_dtbo_size,
+                recovery_dtbo_addr: hdr.recovery_dtbo_addr,
+            },
+            2 => HeaderV0Versioned::V2 {
+                recovery_dtbo_size: hdr.recovery_dtbo_size,
+                recovery_dtbo_addr: hdr.recovery_dtbo_addr,
+                dtb_size: hdr.dtb_size,
+                dtb_addr: hdr.dtb_addr,
+            },
+            version => return Err(Error::UnknownHeaderVersion { version }),
+        };
+        Ok(Self {
+            kernel_size: hdr.kernel_size,
+            kernel_addr: hdr.kernel_addr,
+            ramdisk_size: hdr.ramdisk_size,
+            ramdisk_addr: hdr.ramdisk_addr,
+            second_bootloader_size: hdr.second_bootloader_size,
+            second_bootloader_addr: hdr.second_bootloader_addr,
+            tags_addr: hdr.tags_addr,
+            page_size: hdr.page_size,
+            osversionpatch: OsVersionPatch(hdr.osversionpatch),
+            board_name: hdr.board_name,
+            cmdline_part_1: Box::new(hdr.cmdline_part_1),
+            hash_digest: hdr.hash_digest,
+            cmdline_part_2: Box::new(hdr.cmdline_part_2),
+            versioned,
+            trailing: Vec::new(),
+        })
+    }
+}
+
+/// Parses a boot image header v0-v2 from `len` bytes at `data`.
+///
+/// Returns null if parsing fails or the header version is 3 or higher. Free the
+/// returned header with [`abootimg_free`].
+///
+/// # Safety
+///
+/// `data` must be valid for reads of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn abootimg_parse(data: *const u8, len: usize) -> *mut CHeaderV0 {
+    if data.is_null() {
+        return std::ptr::null_mut();
+    }
+    // SAFETY: The caller guarantees data is valid for len bytes
+    let data = unsafe { std::slice::from_raw_parts(data, len) };
+    match Header::parse(&mut Cursor::new(data)) {
+        Ok(Header::V0(hdr)) => Box::into_raw(Box::new(hdr.to_c())),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// Frees a header returned by [`abootimg_parse`].
+///
+/// # Safety
+///
+/// `hdr` must be null or a pointer returned by [`abootimg_parse`] that hasn't been
+/// freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn abootimg_free(hdr: *mut CHeaderV0) {
+    if !hdr.is_null() {
+        // SAFETY: The caller guarantees hdr came from Box::into_raw in abootimg_parse
+        drop(unsafe { Box::from_raw(hdr) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{build_image, header_v0};
+
+    #[test]
+    fn c_round_trip() {
+        let mut hdr = header_v0(HeaderV0Versioned::V2 {
+            recovery_dtbo_size: 1,
+            recovery_dtbo_addr: 2,
+            dtb_size: 3,
+            dtb_addr: 4,
+        });
+        hdr.set_cmdline(b"console=ttyS0").unwrap();
+        let c = hdr.to_c();
+        assert_eq!(c.header_version, 2);
+        assert_eq!(c.dtb_addr, 4);
+        assert_eq!(HeaderV0::from_c(&c).unwrap(), hdr);
+
+        let image = build_image(&Header::V0(hdr.clone()));
+        unsafe {
+            let parsed = abootimg_parse(image.as_ptr(), image.len());
+            assert!(!parsed.is_null());
+            assert_eq!(*parsed, c);
+            abootimg_free(parsed);
+            assert!(abootimg_parse(image.as_ptr(), 100).is_null());
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::fmt;
+
+/// Format of a file that isn't an Android boot image, recognized by its magic
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ForeignFormat {
+    /// ChromeOS kernel partition
+    ChromeOs,
+    /// ELF executable, such as a bare kernel or bootloader
+    Elf,
+    /// gzip-compressed data
+    Gzip,
+    /// Android sparse image
+    Sparse,
+}
+impl ForeignFormat {
+    /// Detects a foreign format from a file's leading bytes.
+    #[must_use]
+    pub fn detect(magic: &[u8]) -> Option<Self> {
+        match magic {
+            [b'C', b'H', b'R', b'O', b'M', b'E', b'O', b'S', ..] => Some(Self::ChromeOs),
+            [0x7f, b'E', b'L', b'F', ..] => Some(Self::Elf),
+            [0x1f, 0x8b, ..] => Some(Self::Gzip),
+            [0x3a, 0xff, 0x26, 0xed, ..] => Some(Self::Sparse),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ForeignFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::ChromeOs => {
+                "this is a ChromeOS kernel partition; unpack it with `futility vbutil_kernel`"
+            }
+            Self::Elf => {
+                "this is an ELF file, likely a bare kernel or bootloader; pack it with `mkbootimg`"
+            }
+            Self::Gzip => "this is gzip-compressed; decompress it first, e.g. with `gunzip`",
+            Self::Sparse => "this is an Android sparse image; convert it with `simg2img` first",
+        })
+    }
+}
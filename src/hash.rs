@@ -0,0 +1,92 @@
+use std::io::{self, Read};
+
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::{ExtractedImage, HeaderV0};
+
+/// Computes the SHA-256 digest of everything read from `reader`, such as a whole
+/// image file.
+///
+/// This is unrelated to the header's `hash_digest` field.
+pub fn sha256_file<R: Read>(mut reader: R) -> io::Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut reader, &mut hasher)?;
+    Ok(hasher.finalize().into())
+}
+
+impl HeaderV0 {
+    /// Computes the [`hash_digest`](Self::hash_digest) of an image with this header's
+    /// version, as `mkbootimg` does.
+    ///
+    /// This is a SHA-1 digest over each section of the header's version, followed by
+    /// its length as a little-endian `u32`, zero-padded to 32 bytes. The section
+    /// lengths are taken from `image`, not the header.
+    #[must_use]
+    pub fn compute_digest(&self, image: &ExtractedImage) -> [u8; 32] {
+        let mut hasher = Sha1::new();
+        for section in self.sections() {
+            let data = image.section(section.kind);
+            hasher.update(data);
+            hasher.update((data.len() as u32).to_le_bytes());
+        }
+        let mut digest = [0; 32];
+        digest[..20].copy_from_slice(&hasher.finalize());
+        digest
+    }
+    /// Returns `true` if [`hash_digest`](Self::hash_digest) matches the digest of
+    /// `image`'s sections.
+    #[must_use]
+    pub fn verify_digest(&self, image: &ExtractedImage) -> bool {
+        self.hash_digest == self.compute_digest(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::header_v0, write_image, Header, HeaderV0Versioned, SizePolicy};
+
+    #[test]
+    fn known_digest() {
+        assert_eq!(
+            sha256_file(&b"abc"[..]).unwrap(),
+            [
+                0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d, 0xae,
+                0x22, 0x23, 0xb0, 0x03, 0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61,
+                0xf2, 0x00, 0x15, 0xad
+            ]
+        );
+    }
+
+    #[test]
+    fn packed_digest_verifies() {
+        let hdr = Header::V0(header_v0(HeaderV0Versioned::V2 {
+            recovery_dtbo_size: 0,
+            recovery_dtbo_addr: 0,
+            dtb_size: 0,
+            dtb_addr: 0,
+        }));
+        let image = ExtractedImage {
+            kernel: vec![1; 3000],
+            ramdisk: vec![2; 100],
+            dtb: Some(vec![3; 50]),
+            ..Default::default()
+        };
+        let written = write_image(Vec::new(), &hdr, &image, SizePolicy::Recompute).unwrap();
+
+        let mut r = std::io::Cursor::new(&written);
+        let Header::V0(parsed) = Header::parse(&mut r).unwrap() else {
+            unreachable!()
+        };
+        assert_ne!(parsed.hash_digest, [0; 32]);
+        assert_eq!(parsed.hash_digest[20..], [0; 12]);
+        let extracted = Header::V0(parsed.clone()).extract_all(&mut r).unwrap();
+        assert_eq!(extracted, image);
+        assert!(parsed.verify_digest(&extracted));
+
+        let mut changed = extracted;
+        changed.kernel[0] = 0;
+        assert!(!parsed.verify_digest(&changed));
+    }
+}
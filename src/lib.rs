@@ -8,10 +8,67 @@
 
 use binrw::{binrw, io::NoSeek, BinRead, BinWrite};
 
+mod addresses;
+mod any;
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+mod avb;
+mod boot_header;
+mod boot_params;
+mod boot_version;
+mod bootconfig;
+mod compression;
+mod dtb;
+mod error;
+mod extract;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod foreign;
+#[cfg(feature = "hashing")]
+mod hash;
+mod manifest;
+mod mkbootimg;
+mod options;
+mod ota;
+mod section;
+mod signature;
+mod summary;
+mod tag;
 mod vendor;
 mod version;
-pub use vendor::{VendorHeader, VendorHeaderV4};
+mod warning;
+mod writer;
+pub use addresses::{HeaderField, LoadAddresses};
+pub use any::{AnyHeader, ImageCategory};
+pub use avb::{AvbFooter, HashDescriptor};
+pub use boot_header::BootImageHeader;
+pub use boot_params::effective_boot_params;
+pub use boot_version::BootImageVersion;
+pub use bootconfig::BootconfigTrailer;
+pub use compression::{
+    detect_compression, detect_kernel_arch, detect_kernel_format, Compression, KernelArch,
+    KernelFormat,
+};
+pub use dtb::{find_appended_dtb, split_fdts, DtboHeader, FdtHeader, OverlayKind};
+pub use error::{CmdlineTooLong, Error, PairError, ParseOsVersionError};
+pub use extract::ExtractedImage;
+#[cfg(feature = "ffi")]
+pub use ffi::{abootimg_free, abootimg_parse, CHeaderV0};
+pub use foreign::ForeignFormat;
+#[cfg(feature = "hashing")]
+pub use hash::sha256_file;
+pub use manifest::{Manifest, ManifestSection};
+pub use mkbootimg::SectionPaths;
+pub use options::{ParseOptions, SizeUnit};
+pub use ota::read_ota_partition_names;
+pub use section::{Region, Section, SectionKind};
+pub use signature::{attach_image_signature, strip_image_signature};
+pub use summary::HeaderSummary;
+pub use tag::{read_trailing_tag, TrailingTag};
+pub use vendor::{validate_pair, VendorHeader, VendorHeaderV4, VendorRamdiskTableEntry};
 pub use version::{OsPatch, OsVersion, OsVersionPatch};
+pub use warning::Warning;
+pub use writer::{write_image, ImageWriter, SizePolicy};
 
 /// Android boot image header versions 0, 1 and 2
 ///
@@ -38,6 +95,10 @@ pub use version::{OsPatch, OsVersion, OsVersionPatch};
 /// │+ padding to page size   │
 /// └─────────────────────────┘
 /// ```
+///
+/// A section with a size of zero is empty and takes no space. Its position is still
+/// returned and equals the position of the following section. Position functions
+/// return `None` only if the header's version doesn't have the section.
 #[binrw]
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 #[brw(little, magic = b"ANDROID!")]
@@ -74,7 +135,15 @@ pub struct HeaderV0 {
     pub cmdline_part_2: Box<[u8; 1024]>,
     /// Version-specific part of the boot image header.
     #[br(args(header_version))]
+    #[bw(args(self.trailing.len() as u32))]
     pub versioned: HeaderV0Versioned,
+    /// Header bytes past the fields known to this crate, from a newer header
+    /// declaring a larger size (v1-v2).
+    ///
+    /// Only kept with [`ParseOptions::allow_newer`]. Written back after the known
+    /// fields and counted in the written header size.
+    #[br(ignore)]
+    pub trailing: Vec<u8>,
 }
 // TODO: store cmdline as one contiguous [u8; 1536]
 impl HeaderV0 {
@@ -84,6 +153,7 @@ impl HeaderV0 {
         (page_size - (size & (page_size - 1))) & (page_size - 1)
     }
     /// Returns the boot image header's version number.
+    #[must_use]
     pub fn header_version(&self) -> u32 {
         match self.versioned {
             HeaderV0Versioned::V0 => 0,
@@ -91,57 +161,254 @@ impl HeaderV0 {
             HeaderV0Versioned::V2 { .. } => 2,
         }
     }
+    fn header_size(&self) -> u32 {
+        let known = match self.versioned {
+            HeaderV0Versioned::V0 => 1632,
+            HeaderV0Versioned::V1 { .. } => 1648,
+            HeaderV0Versioned::V2 { .. } => 1660,
+        };
+        known + self.trailing.len() as u32
+    }
+    /// Switches the header to version `target` (0-2) in place.
+    ///
+    /// Fields new to `target` are zeroed. Downgrading fails without changing the
+    /// header if it would drop a non-zero field. Versions 3 and 4 have a different
+    /// layout and fail with [`Error::UnsupportedHeaderVersion`].
+    pub fn set_version(&mut self, target: u32) -> Result<(), Error> {
+        let (recovery_dtbo_size, recovery_dtbo_addr, dtb_size, dtb_addr) = self.versioned.fields();
+        let (versioned, dropped) = match target {
+            0 => (
+                HeaderV0Versioned::V0,
+                recovery_dtbo_size != 0
+                    || recovery_dtbo_addr != 0
+                    || dtb_size != 0
+                    || dtb_addr != 0,
+            ),
+            1 => (
+                HeaderV0Versioned::V1 {
+                    recovery_dtbo_size,
+                    recovery_dtbo_addr,
+                },
+                dtb_size != 0 || dtb_addr != 0,
+            ),
+            2 => (
+                HeaderV0Versioned::V2 {
+                    recovery_dtbo_size,
+                    recovery_dtbo_addr,
+                    dtb_size,
+                    dtb_addr,
+                },
+                false,
+            ),
+            3..=4 => return Err(Error::UnsupportedHeaderVersion { version: target }),
+            version => return Err(Error::UnknownHeaderVersion { version }),
+        };
+        if dropped {
+            return Err(Error::LossyDowngrade { version: target });
+        }
+        self.versioned = versioned;
+        Ok(())
+    }
+    /// Returns the base kernel command line, trimmed at its null terminator.
+    ///
+    /// This is the first 512 bytes of the command line.
+    #[must_use]
+    pub fn base_cmdline(&self) -> &[u8] {
+        take_until_null(&*self.cmdline_part_1)
+    }
+    /// Returns the extra kernel command line, trimmed at its null terminator.
+    ///
+    /// This is the 1024 bytes following the base command line. Some legacy tools
+    /// treat it separately, but the kernel sees it appended directly to
+    /// [`base_cmdline`](Self::base_cmdline).
+    #[must_use]
+    pub fn extra_cmdline(&self) -> &[u8] {
+        take_until_null(&*self.cmdline_part_2)
+    }
+    /// Returns the full kernel command line.
+    ///
+    /// This is [`base_cmdline`](Self::base_cmdline) followed by
+    /// [`extra_cmdline`](Self::extra_cmdline).
+    #[must_use]
+    pub fn cmdline(&self) -> Vec<u8> {
+        [self.base_cmdline(), self.extra_cmdline()].concat()
+    }
+    /// Returns the number of command line bytes in use, up to the null terminators.
+    ///
+    /// This is the length of [`cmdline`](Self::cmdline), and equals
+    /// [`cmdline_capacity`](Self::cmdline_capacity) if neither part is terminated.
+    #[must_use]
+    pub fn cmdline_used_len(&self) -> usize {
+        self.base_cmdline().len() + self.extra_cmdline().len()
+    }
+    /// Returns the size of the command line fields, which is 1536 bytes.
+    #[must_use]
+    pub fn cmdline_capacity(&self) -> usize {
+        self.cmdline_part_1.len() + self.cmdline_part_2.len()
+    }
+    /// Sets the full kernel command line.
+    ///
+    /// Like `mkbootimg`, the first 511 bytes go to the base command line and the rest
+    /// to the extra command line, each null-terminated. See [`split_cmdline`].
+    pub fn set_cmdline(&mut self, cmdline: &[u8]) -> Result<(), CmdlineTooLong> {
+        (self.cmdline_part_1, self.cmdline_part_2) = split_cmdline(cmdline)?;
+        Ok(())
+    }
     /// Returns the kernel's position in the boot image.
+    ///
+    /// This is the header size of the header's version padded to the page size.
+    #[must_use]
     pub fn kernel_position(&self) -> usize {
-        1660 + self.get_padding(1660)
+        let header_size = self.header_size() as usize;
+        header_size + self.get_padding(header_size)
     }
     /// Returns the ramdisk's position in the boot image.
+    #[must_use]
     pub fn ramdisk_position(&self) -> usize {
         self.kernel_position()
             + self.kernel_size as usize
             + self.get_padding(self.kernel_size as usize)
     }
     /// Returns the second stage bootloader's position in the boot image.
+    #[must_use]
     pub fn second_bootloader_position(&self) -> usize {
         self.ramdisk_position()
             + self.ramdisk_size as usize
             + self.get_padding(self.ramdisk_size as usize)
     }
     /// Returns the recovery DTBO's position in the boot image.
-    pub fn recovery_dtbo_position(&self) -> usize {
-        self.second_bootloader_position()
-            + self.second_bootloader_size as usize
-            + self.get_padding(self.second_bootloader_size as usize)
+    ///
+    /// This returns `None` at version 0.
+    #[must_use]
+    pub fn recovery_dtbo_position(&self) -> Option<usize> {
+        match self.versioned {
+            HeaderV0Versioned::V0 => None,
+            HeaderV0Versioned::V1 { .. } | HeaderV0Versioned::V2 { .. } => Some(
+                self.second_bootloader_position()
+                    + self.second_bootloader_size as usize
+                    + self.get_padding(self.second_bootloader_size as usize),
+            ),
+        }
     }
     /// Returns the DTB's position in the boot image.
     ///
-    /// This returns `None` at version 0.
+    /// This returns `None` at versions 0 and 1.
+    #[must_use]
     pub fn dtb_position(&self) -> Option<usize> {
         match self.versioned {
-            HeaderV0Versioned::V0 => None,
-            HeaderV0Versioned::V1 {
-                recovery_dtbo_size, ..
-            }
-            | HeaderV0Versioned::V2 {
+            HeaderV0Versioned::V0 | HeaderV0Versioned::V1 { .. } => None,
+            HeaderV0Versioned::V2 {
                 recovery_dtbo_size, ..
             } => Some(
-                self.second_bootloader_position()
+                self.recovery_dtbo_position()?
                     + recovery_dtbo_size as usize
                     + self.get_padding(recovery_dtbo_size as usize),
             ),
         }
     }
+    /// Reads the DTB section and splits it into individual DTBs.
+    ///
+    /// Returns an empty vector if the DTB section is empty or the header's version
+    /// doesn't have it.
+    pub fn read_dtbs<R: std::io::Read + std::io::Seek>(
+        &self,
+        reader: &mut R,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        let Some(section) = self
+            .sections()
+            .into_iter()
+            .find(|s| s.kind == SectionKind::Dtb)
+        else {
+            return Ok(Vec::new());
+        };
+        let dtb = extract::read_section(reader, section)?;
+        Ok(split_fdts(&dtb)?.into_iter().map(<[u8]>::to_vec).collect())
+    }
+    /// Reads the leading magic of the recovery DTBO/ACPIO section to tell device
+    /// tree overlays from ACPI overlays.
+    ///
+    /// Returns [`OverlayKind::Unknown`] if the section is empty or the header's
+    /// version doesn't have it.
+    pub fn recovery_overlay_kind<R: std::io::Read + std::io::Seek>(
+        &self,
+        reader: &mut R,
+    ) -> Result<OverlayKind, Error> {
+        let (size, _, _, _) = self.versioned.fields();
+        let Some(position) = self.recovery_dtbo_position() else {
+            return Ok(OverlayKind::Unknown);
+        };
+        let mut magic = peek_magic(reader, position as u64)?;
+        magic.truncate(size as usize);
+        Ok(OverlayKind::detect(&magic))
+    }
+    /// Returns the sections present in this header version, in on-disk order.
+    ///
+    /// Sections with a size of zero are included.
+    #[must_use]
+    pub fn sections(&self) -> Vec<Section> {
+        let mut sections = vec![
+            Section {
+                kind: SectionKind::Kernel,
+                position: self.kernel_position(),
+                size: self.kernel_size,
+            },
+            Section {
+                kind: SectionKind::Ramdisk,
+                position: self.ramdisk_position(),
+                size: self.ramdisk_size,
+            },
+            Section {
+                kind: SectionKind::Second,
+                position: self.second_bootloader_position(),
+                size: self.second_bootloader_size,
+            },
+        ];
+        match self.versioned {
+            HeaderV0Versioned::V0 => {}
+            HeaderV0Versioned::V1 {
+                recovery_dtbo_size, ..
+            } => sections.push(Section {
+                kind: SectionKind::RecoveryDtbo,
+                position: self.recovery_dtbo_position().unwrap(),
+                size: recovery_dtbo_size,
+            }),
+            HeaderV0Versioned::V2 {
+                recovery_dtbo_size,
+                dtb_size,
+                ..
+            } => sections.extend([
+                Section {
+                    kind: SectionKind::RecoveryDtbo,
+                    position: self.recovery_dtbo_position().unwrap(),
+                    size: recovery_dtbo_size,
+                },
+                Section {
+                    kind: SectionKind::Dtb,
+                    position: self.dtb_position().unwrap(),
+                    size: dtb_size,
+                },
+            ]),
+        }
+        sections
+    }
 }
 
 /// Version-specific part of boot image headers v0-v2
 #[binrw]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[br(import(header_version: u32))]
+#[bw(import(trailing_len: u32))]
 pub enum HeaderV0Versioned {
     /// V0-specific fields
     #[br(pre_assert(header_version == 0))]
     V0,
     /// V1-specific fields
+    ///
+    /// The expected header size is 1648 bytes. [`Header::parse`] fails with
+    /// [`Error::HeaderSizeMismatch`] otherwise, e.g. for 1632 bytes, the size of a v0
+    /// header. Larger sizes are only accepted with
+    /// [`ParseOptions::allow_newer`](crate::ParseOptions::allow_newer), which keeps
+    /// the extra bytes as unknown trailing fields.
     #[br(pre_assert(header_version == 1))]
     V1 {
         /// Recovery DTBO/ACPIO size
@@ -149,7 +416,7 @@ pub enum HeaderV0Versioned {
         /// Recovery DTBO/ACPIO physical load address
         recovery_dtbo_addr: u64,
         #[br(temp, assert(header_size == 1648))]
-        #[bw(calc = 1648)]
+        #[bw(calc = 1648 + trailing_len)]
         header_size: u32,
     },
     /// V2-specific fields
@@ -160,7 +427,7 @@ pub enum HeaderV0Versioned {
         /// Recovery DTBO/ACPIO physical load address
         recovery_dtbo_addr: u64,
         #[br(temp, assert(header_size == 1660))]
-        #[bw(calc = 1660)]
+        #[bw(calc = 1660 + trailing_len)]
         header_size: u32,
         /// DTB size
         dtb_size: u32,
@@ -168,6 +435,25 @@ pub enum HeaderV0Versioned {
         dtb_addr: u64,
     },
 }
+impl HeaderV0Versioned {
+    /// Returns the recovery DTBO size and address and the DTB size and address,
+    /// which are zero if absent.
+    fn fields(&self) -> (u32, u64, u32, u64) {
+        match *self {
+            Self::V0 => (0, 0, 0, 0),
+            Self::V1 {
+                recovery_dtbo_size,
+                recovery_dtbo_addr,
+            } => (recovery_dtbo_size, recovery_dtbo_addr, 0, 0),
+            Self::V2 {
+                recovery_dtbo_size,
+                recovery_dtbo_addr,
+                dtb_size,
+                dtb_addr,
+            } => (recovery_dtbo_size, recovery_dtbo_addr, dtb_size, dtb_addr),
+        }
+    }
+}
 
 /// Android boot image header versions 3 and 4
 ///
@@ -215,11 +501,23 @@ pub struct HeaderV3 {
     /// This is only present in version 4 and the version will be inferred from this field.
     #[br(if(header_version == 4))]
     pub v4_signature_size: Option<u32>,
+    /// Header bytes past the fields known to this crate, from a newer header
+    /// declaring a larger size.
+    ///
+    /// Only kept with [`ParseOptions::allow_newer`]. Written back after the known
+    /// fields and counted in the written header size.
+    #[br(ignore)]
+    pub trailing: Vec<u8>,
 }
 impl HeaderV3 {
     const PAGE_SIZE: usize = 4096;
+    /// Largest boot signature size accepted by [`Header::validate`].
+    ///
+    /// `mkbootimg` always reserves 4096 bytes, so this leaves plenty of headroom.
+    pub const MAX_SIGNATURE_SIZE: u32 = 0x10000;
 
     /// Returns the boot image header's version number.
+    #[must_use]
     pub fn header_version(&self) -> u32 {
         if self.v4_signature_size.is_some() {
             4
@@ -228,33 +526,81 @@ impl HeaderV3 {
         }
     }
     fn header_size(&self) -> u32 {
-        if self.v4_signature_size.is_some() {
+        let known = if self.v4_signature_size.is_some() {
             1584
         } else {
             1580
+        };
+        known + self.trailing.len() as u32
+    }
+    /// Sets the kernel command line.
+    pub fn set_cmdline(&mut self, cmdline: &[u8]) -> Result<(), CmdlineTooLong> {
+        let max = self.cmdline.len() - 1;
+        if cmdline.len() > max {
+            return Err(CmdlineTooLong {
+                len: cmdline.len(),
+                max,
+            });
         }
+        self.cmdline.fill(0);
+        self.cmdline[..cmdline.len()].copy_from_slice(cmdline);
+        Ok(())
     }
     fn get_padding(size: usize) -> usize {
         (Self::PAGE_SIZE - (size & (Self::PAGE_SIZE - 1))) & (Self::PAGE_SIZE - 1)
     }
+    /// Returns `len` rounded up to the page size, which is 4096.
+    #[must_use]
+    pub fn pad_to_page(len: usize) -> usize {
+        len + Self::get_padding(len)
+    }
     /// Returns the kernel's position in the boot image.
     ///
     /// Hardcoded to the page size, which is 4096.
+    #[must_use]
     pub const fn kernel_position() -> usize {
         Self::PAGE_SIZE
     }
     /// Returns the ramdisk's position in the boot image.
+    #[must_use]
     pub fn ramdisk_position(&self) -> usize {
         Self::kernel_position()
             + self.kernel_size as usize
             + Self::get_padding(self.kernel_size as usize)
     }
     /// Returns the boot signature's position in the boot image.
+    #[must_use]
     pub fn bootsig_position(&self) -> usize {
         self.ramdisk_position()
             + self.ramdisk_size as usize
             + Self::get_padding(self.ramdisk_size as usize)
     }
+    /// Returns the sections present in this header version, in on-disk order.
+    ///
+    /// Sections with a size of zero are included.
+    #[must_use]
+    pub fn sections(&self) -> Vec<Section> {
+        let mut sections = vec![
+            Section {
+                kind: SectionKind::Kernel,
+                position: Self::kernel_position(),
+                size: self.kernel_size,
+            },
+            Section {
+                kind: SectionKind::Ramdisk,
+                position: self.ramdisk_position(),
+                size: self.ramdisk_size,
+            },
+        ];
+        if let Some(size) = self.v4_signature_size {
+            sections.push(Section {
+                kind: SectionKind::BootSignature,
+                position: self.bootsig_position(),
+                size,
+            });
+        }
+        sections
+    }
 }
 
 /// Android boot image header for versions 0 through 4
@@ -267,23 +613,248 @@ pub enum Header {
 }
 impl Header {
     /// Parses an Android boot image header from a reader.
-    pub fn parse<R: std::io::Read + std::io::Seek>(reader: &mut R) -> Result<Self, binrw::Error> {
-        reader.seek(std::io::SeekFrom::Start(0x28))?;
+    ///
+    /// Fails with [`Error::TooSmall`] if the reader is shorter than the header.
+    ///
+    /// A [`BufReader`](std::io::BufReader) works well here. Read the sections through
+    /// the same `BufReader` too, seeking it rather than its inner reader, so that its
+    /// buffer is discarded instead of going stale:
+    ///
+    /// ```no_run
+    /// # use std::{fs::File, io::{BufReader, Read, Seek, SeekFrom}};
+    /// # use abootimg_oxide::Header;
+    /// # fn f() -> Result<(), abootimg_oxide::Error> {
+    /// let mut r = BufReader::new(File::open("boot.img")?);
+    /// let hdr = Header::parse(&mut r)?;
+    /// r.seek(SeekFrom::Start(hdr.kernel_position() as u64))?;
+    /// let mut kernel = Vec::new();
+    /// (&mut r).take(hdr.kernel_size() as u64).read_to_end(&mut kernel)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn parse<R: std::io::Read + std::io::Seek>(reader: &mut R) -> Result<Self, Error> {
+        Self::parse_at(reader, 0)
+    }
+    /// Parses an Android boot image header from a reader, returning it along with its
+    /// length in bytes, excluding padding.
+    ///
+    /// The length is [`header_len`](Self::header_len), i.e. where the header ends, for
+    /// composing with parsers of other formats.
+    pub fn parse_with_len<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+    ) -> Result<(Self, usize), Error> {
+        let hdr = Self::parse(reader)?;
+        let len = hdr.header_len();
+        Ok((hdr, len))
+    }
+    /// Parses an Android boot image header starting at `offset` in a reader.
+    ///
+    /// This is useful for images wrapped in a vendor-specific header; see
+    /// [`find_android_magic`]. Positions returned by the header are relative to
+    /// `offset`.
+    ///
+    /// A v0-v2 page size that isn't a power of two is rejected with
+    /// [`Error::InvalidPageSize`].
+    pub fn parse_at<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        offset: u64,
+    ) -> Result<Self, Error> {
+        // Smallest header of any version, needed before the version can be read
+        const MIN_HEADER_SIZE: u64 = 1580;
+
+        let have = reader
+            .seek(std::io::SeekFrom::End(0))?
+            .saturating_sub(offset);
+        let magic = peek_magic(reader, offset)?;
+        if let Some(format) = ForeignFormat::detect(&magic) {
+            return Err(Error::ForeignFormat(format));
+        }
+        if let Ok(magic) = <[u8; 8]>::try_from(magic) {
+            // Anything less alike is left to fail as a bad magic
+            let matching = magic_similarity(&magic);
+            if (6..8).contains(&matching) {
+                return Err(Error::CorruptedMagic { matching });
+            }
+        }
+        if have < 0x2c {
+            return Err(Error::TooSmall {
+                need: MIN_HEADER_SIZE,
+                have,
+            });
+        }
+        reader.seek(std::io::SeekFrom::Start(offset + 0x28))?;
         let mut version_buf = [0u8; 4];
         reader.read_exact(&mut version_buf)?;
-        reader.seek(std::io::SeekFrom::Start(0))?;
+        reader.seek(std::io::SeekFrom::Start(offset))?;
 
-        Ok(match u32::from_le_bytes(version_buf) {
-            0..=2 => Self::V0(HeaderV0::read(reader)?),
-            3 | 4 => Self::V3(HeaderV3::read(reader)?),
-            version => {
-                return Err(binrw::Error::AssertFail {
-                    pos: 0x28,
-                    message: format!("Unknown header version: {}", version),
-                })
+        let version = u32::from_le_bytes(version_buf);
+        let need = match version {
+            0 => 1632,
+            1 => 1648,
+            2 => 1660,
+            3 => 1580,
+            4 => 1584,
+            version => return Err(Error::UnknownHeaderVersion { version }),
+        };
+        if have < need {
+            return Err(Error::TooSmall { need, have });
+        }
+        // AOSP's mkbootimg has always written these exact sizes, so a mismatch means
+        // the image is malformed or the version field is wrong
+        if let Some((expected, size_offset)) = header_size_field(version) {
+            let actual = read_u32_at(reader, offset + size_offset)?;
+            if actual != expected {
+                return Err(Error::HeaderSizeMismatch {
+                    version,
+                    expected,
+                    actual,
+                });
+            }
+            reader.seek(std::io::SeekFrom::Start(offset))?;
+        }
+        Ok(match version {
+            0..=2 => {
+                let hdr = HeaderV0::read(reader)?;
+                // Every position and padding computation divides by the page size
+                if !hdr.page_size.is_power_of_two() {
+                    return Err(Error::InvalidPageSize {
+                        page_size: hdr.page_size,
+                    });
+                }
+                Self::V0(hdr)
             }
+            _ => Self::V3(HeaderV3::read(reader)?),
         })
     }
+    /// Parses an Android boot image header from a reader with options.
+    ///
+    /// With [`SizeUnit::Sectors`], the section sizes are converted to bytes, so the
+    /// returned header's positions and sizes are in bytes.
+    pub fn parse_with_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        options: &ParseOptions,
+    ) -> Result<Self, Error> {
+        let mut hdr = if options.allow_newer {
+            Self::parse_newer(reader)?
+        } else {
+            Self::parse(reader)?
+        };
+        if options.size_unit == SizeUnit::Sectors {
+            let sizes: Vec<&mut u32> = match &mut hdr {
+                Self::V0(hdr) => {
+                    let mut sizes = vec![
+                        &mut hdr.kernel_size,
+                        &mut hdr.ramdisk_size,
+                        &mut hdr.second_bootloader_size,
+                    ];
+                    match &mut hdr.versioned {
+                        HeaderV0Versioned::V0 => {}
+                        HeaderV0Versioned::V1 {
+                            recovery_dtbo_size, ..
+                        } => sizes.push(recovery_dtbo_size),
+                        HeaderV0Versioned::V2 {
+                            recovery_dtbo_size,
+                            dtb_size,
+                            ..
+                        } => sizes.extend([recovery_dtbo_size, dtb_size]),
+                    }
+                    sizes
+                }
+                Self::V3(hdr) => [&mut hdr.kernel_size, &mut hdr.ramdisk_size]
+                    .into_iter()
+                    .chain(hdr.v4_signature_size.as_mut())
+                    .collect(),
+            };
+            for size in sizes {
+                *size = size.checked_mul(512).ok_or(Error::SizeOverflow)?;
+            }
+        }
+        if let Some(max) = options.max_section_size {
+            hdr.check_section_sizes(max)?;
+        }
+        Ok(hdr)
+    }
+    /// Parses a header that may declare a larger size than known, keeping the extra
+    /// bytes in its `trailing` field.
+    ///
+    /// A v3/v4 header can't grow past its fixed 4096-byte page, where the kernel
+    /// starts.
+    fn parse_newer<R: std::io::Read + std::io::Seek>(reader: &mut R) -> Result<Self, Error> {
+        use std::io::Read as _;
+
+        let version = read_u32_at(reader, 0x28)?;
+        let Some((known, size_offset)) = header_size_field(version) else {
+            return Self::parse(reader);
+        };
+        let header_size = read_u32_at(reader, size_offset)?;
+        if header_size <= known {
+            return Self::parse(reader);
+        }
+        if version >= 3 && header_size as usize > HeaderV3::PAGE_SIZE {
+            return Err(Error::HeaderSizeMismatch {
+                version,
+                expected: known,
+                actual: header_size,
+            });
+        }
+
+        let mut header = vec![0; known as usize];
+        reader.seek(std::io::SeekFrom::Start(0))?;
+        reader.read_exact(&mut header)?;
+        header[size_offset as usize..][..4].copy_from_slice(&known.to_le_bytes());
+        let mut hdr = Self::parse(&mut std::io::Cursor::new(header))?;
+
+        let extra = u64::from(header_size - known);
+        let mut trailing = Vec::new();
+        (&mut *reader).take(extra).read_to_end(&mut trailing)?;
+        if trailing.len() as u64 != extra {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        match &mut hdr {
+            Self::V0(hdr) => hdr.trailing = trailing,
+            Self::V3(hdr) => hdr.trailing = trailing,
+        }
+        Ok(hdr)
+    }
+    /// Parses an Android boot image header from a reader, collecting non-fatal
+    /// [`Warning`]s about it.
+    pub fn parse_with_warnings<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+    ) -> Result<(Self, Vec<Warning>), Error> {
+        let hdr = Self::parse(reader)?;
+        let mut warnings = Vec::new();
+        match &hdr {
+            Self::V0(v0) => {
+                if !v0.cmdline_part_2.contains(&0) {
+                    warnings.push(Warning::UnterminatedCmdline);
+                }
+                if !v0.board_name.contains(&0) {
+                    warnings.push(Warning::UnterminatedBoardName);
+                }
+                if !matches!(v0.page_size, 2048 | 4096 | 8192 | 16384) {
+                    warnings.push(Warning::UnusualPageSize {
+                        page_size: v0.page_size,
+                    });
+                }
+                let (size, addr, _, _) = v0.versioned.fields();
+                if v0.versioned != HeaderV0Versioned::V0 && (size == 0) != (addr == 0) {
+                    warnings.push(Warning::InconsistentRecoveryDtbo { size, addr });
+                }
+            }
+            Self::V3(v3) => {
+                if !v3.cmdline.contains(&0) {
+                    warnings.push(Warning::UnterminatedCmdline);
+                }
+                reader.seek(std::io::SeekFrom::Start(24))?;
+                let mut reserved = [0; 16];
+                reader.read_exact(&mut reserved)?;
+                if reserved != [0; 16] {
+                    warnings.push(Warning::NonZeroReserved);
+                }
+            }
+        }
+        Ok((hdr, warnings))
+    }
     /// Serializes an Android boot image header to a writer.
     ///
     /// Note that you must write the kernel, ramdisk, etc. yourself.
@@ -295,13 +866,20 @@ impl Header {
         }
     }
     /// Returns the boot image header's version number.
+    #[must_use]
     pub fn header_version(&self) -> u32 {
         match self {
             Self::V0(hdr) => hdr.header_version(),
             Self::V3(hdr) => hdr.header_version(),
         }
     }
+    /// Returns the boot image header's version.
+    #[must_use]
+    pub fn version(&self) -> BootImageVersion {
+        BootImageVersion::try_from(self.header_version()).unwrap()
+    }
     /// Returns the boot image header's OS version and patch level.
+    #[must_use]
     pub fn osversionpatch(&self) -> OsVersionPatch {
         match self {
             Self::V0(hdr) => hdr.osversionpatch,
@@ -309,6 +887,27 @@ impl Header {
         }
     }
     /// Returns the kernel's position in the boot image.
+    ///
+    /// For every version, the kernel starts at the first page boundary at or after
+    /// the end of the header:
+    ///
+    /// ```
+    /// # use std::io::Cursor;
+    /// # use abootimg_oxide::Header;
+    /// // A version 0 header with a page size of 2048 bytes
+    /// let mut image = b"ANDROID!".to_vec();
+    /// image.resize(0x24, 0);
+    /// image.extend(2048u32.to_le_bytes());
+    /// image.resize(2048, 0);
+    /// let hdr = Header::parse(&mut Cursor::new(image)).unwrap();
+    ///
+    /// assert_eq!(hdr.header_len(), 1632);
+    /// assert_eq!(hdr.kernel_position(), 2048);
+    /// assert!(hdr.kernel_position() >= hdr.header_len());
+    /// assert_eq!(hdr.kernel_position() % hdr.page_size(), 0);
+    /// assert!(hdr.kernel_position() - hdr.header_len() < hdr.page_size());
+    /// ```
+    #[must_use]
     pub fn kernel_position(&self) -> usize {
         match self {
             Self::V0(hdr) => hdr.kernel_position(),
@@ -316,6 +915,7 @@ impl Header {
         }
     }
     /// Returns the kernel's size.
+    #[must_use]
     pub fn kernel_size(&self) -> u32 {
         match self {
             Self::V0(hdr) => hdr.kernel_size,
@@ -323,6 +923,7 @@ impl Header {
         }
     }
     /// Returns the ramdisk's position in the boot image.
+    #[must_use]
     pub fn ramdisk_position(&self) -> usize {
         match self {
             Self::V0(hdr) => hdr.ramdisk_position(),
@@ -330,6 +931,7 @@ impl Header {
         }
     }
     /// Returns the ramdisk's size.
+    #[must_use]
     pub fn ramdisk_size(&self) -> u32 {
         match self {
             Self::V0(hdr) => hdr.ramdisk_size,
@@ -337,12 +939,1589 @@ impl Header {
         }
     }
     /// Returns the page size in bytes.
+    #[must_use]
     pub fn page_size(&self) -> usize {
         match self {
             Self::V0(hdr) => hdr.page_size as usize,
             Self::V3(_) => HeaderV3::PAGE_SIZE,
         }
     }
+    /// Returns `len` rounded up to the page size, i.e. `len` plus the padding that
+    /// would follow a section of `len` bytes.
+    #[must_use]
+    pub fn pad_to_page(&self, len: usize) -> usize {
+        match self {
+            Self::V0(hdr) => len + hdr.get_padding(len),
+            Self::V3(_) => HeaderV3::pad_to_page(len),
+        }
+    }
+    /// Returns the kernel command line, trimmed at its null terminator.
+    ///
+    /// For versions 0-2, this is [`HeaderV0::cmdline`].
+    #[must_use]
+    pub fn cmdline(&self) -> Vec<u8> {
+        match self {
+            Self::V0(hdr) => hdr.cmdline(),
+            Self::V3(hdr) => take_until_null(&*hdr.cmdline).to_vec(),
+        }
+    }
+    /// Returns the length of the header in bytes, excluding padding.
+    ///
+    /// This is 1632, 1648, 1660, 1580 or 1584 bytes for versions 0-4.
+    #[must_use]
+    pub fn header_len(&self) -> usize {
+        match self {
+            Self::V0(hdr) => hdr.header_size() as usize,
+            Self::V3(hdr) => hdr.header_size() as usize,
+        }
+    }
+    /// Returns the canonical header size of the header's version: 1632, 1648, 1660,
+    /// 1580 or 1584 bytes for versions 0-4.
+    ///
+    /// Versions 1-4 store this in the header size field, which [`Header::parse`]
+    /// checks. Unlike [`header_len`](Self::header_len), unknown trailing fields aren't
+    /// counted.
+    #[must_use]
+    pub fn expected_header_size(&self) -> u32 {
+        // Version 0 has no header size field
+        header_size_field(self.header_version()).map_or(1632, |(size, _)| size)
+    }
+    /// Returns the regions of the boot image, starting with the header.
+    ///
+    /// Sections with a size of zero are included.
+    #[must_use]
+    pub fn layout(&self) -> Vec<Region> {
+        let header_size = self.header_len() as u64;
+        let page_size = self.page_size() as u64;
+        let padding = |size: u64| size.next_multiple_of(page_size) - size;
+
+        let mut regions = vec![Region {
+            name: "header",
+            offset: 0,
+            size: header_size,
+            padding: padding(header_size),
+        }];
+        regions.extend(self.sections().into_iter().map(|section| Region {
+            name: section.kind.name(),
+            offset: section.position as u64,
+            size: section.size as u64,
+            padding: padding(section.size as u64),
+        }));
+        regions
+    }
+    /// Sets the kernel command line.
+    ///
+    /// For versions 0-2, see [`HeaderV0::set_cmdline`].
+    pub fn set_cmdline(&mut self, cmdline: &[u8]) -> Result<(), CmdlineTooLong> {
+        match self {
+            Self::V0(hdr) => hdr.set_cmdline(cmdline),
+            Self::V3(hdr) => hdr.set_cmdline(cmdline),
+        }
+    }
+    /// Returns a copy of the header with the kernel command line replaced.
+    pub fn with_cmdline(&self, cmdline: &[u8]) -> Result<Self, Error> {
+        let mut hdr = self.clone();
+        hdr.set_cmdline(cmdline)?;
+        Ok(hdr)
+    }
+    /// Returns a copy of the header with the OS version replaced.
+    pub fn with_os_version(&self, version: OsVersion) -> Result<Self, Error> {
+        if version.0 >= 1 << 21 {
+            return Err(Error::InvalidOsVersion(version));
+        }
+        let mut hdr = self.clone();
+        let osversionpatch = hdr.osversionpatch_mut();
+        *osversionpatch = OsVersionPatch::new(version, osversionpatch.patch());
+        Ok(hdr)
+    }
+    /// Returns a copy of the header with the OS patch level replaced.
+    pub fn with_os_patch(&self, patch: OsPatch) -> Result<Self, Error> {
+        if patch.0 >= 1 << 11 || patch.month() > 12 {
+            return Err(Error::InvalidOsPatch(patch));
+        }
+        let mut hdr = self.clone();
+        let osversionpatch = hdr.osversionpatch_mut();
+        *osversionpatch = OsVersionPatch::new(osversionpatch.version(), patch);
+        Ok(hdr)
+    }
+    /// Returns a copy of the header with the page size replaced.
+    ///
+    /// The page size must be a power of two. Versions 3 and 4 always use 4096 bytes, so
+    /// for them only 4096 is accepted, returning an unchanged copy.
+    pub fn with_page_size(&self, page_size: u32) -> Result<Self, Error> {
+        match self {
+            Self::V0(hdr) if page_size.is_power_of_two() => Ok(Self::V0(HeaderV0 {
+                page_size,
+                ..hdr.clone()
+            })),
+            Self::V3(_) if page_size as usize == HeaderV3::PAGE_SIZE => Ok(self.clone()),
+            _ => Err(Error::InvalidPageSize { page_size }),
+        }
+    }
+    fn osversionpatch_mut(&mut self) -> &mut OsVersionPatch {
+        match self {
+            Self::V0(hdr) => &mut hdr.osversionpatch,
+            Self::V3(hdr) => &mut hdr.osversionpatch,
+        }
+    }
+    /// Zeroes the bytes after the null terminators of the command line and board
+    /// name, which don't affect the boot.
+    ///
+    /// Headers differing only in such bytes serialize identically after this. The
+    /// reserved bytes of v3/v4 headers are always written as zeroes.
+    pub fn normalize(&mut self) {
+        match self {
+            Self::V0(hdr) => {
+                zero_after_null(&mut hdr.board_name);
+                zero_after_null(&mut *hdr.cmdline_part_1);
+                zero_after_null(&mut *hdr.cmdline_part_2);
+            }
+            Self::V3(hdr) => zero_after_null(&mut *hdr.cmdline),
+        }
+    }
+    /// Compares headers, ignoring the bytes that [`normalize`](Self::normalize)
+    /// zeroes.
+    ///
+    /// Unlike `==`, this treats headers that boot identically as equal.
+    #[must_use]
+    pub fn semantic_eq(&self, other: &Header) -> bool {
+        let (mut a, mut b) = (self.clone(), other.clone());
+        a.normalize();
+        b.normalize();
+        a == b
+    }
+    /// Returns the sections present in this header version, in on-disk order.
+    ///
+    /// Sections with a size of zero are included.
+    #[must_use]
+    pub fn sections(&self) -> Vec<Section> {
+        match self {
+            Self::V0(hdr) => hdr.sections(),
+            Self::V3(hdr) => hdr.sections(),
+        }
+    }
+    /// Returns a section, or `None` if the header's version doesn't have it.
+    #[must_use]
+    pub fn section(&self, kind: SectionKind) -> Option<Section> {
+        self.sections()
+            .into_iter()
+            .find(|section| section.kind == kind)
+    }
+    /// Returns the byte range of a section in the boot image, excluding padding.
+    ///
+    /// Returns `None` if the header's version doesn't have the section, or an empty
+    /// range if the section is empty.
+    #[must_use]
+    pub fn section_range(&self, kind: SectionKind) -> Option<std::ops::Range<u64>> {
+        let section = self.section(kind)?;
+        let start = section.position as u64;
+        Some(start..start + section.size as u64)
+    }
+    /// Returns the number of padding bytes between the end of a section and the next
+    /// page boundary.
+    ///
+    /// Returns 0 if the section is empty or the header's version doesn't have it.
+    #[must_use]
+    pub fn trailing_padding(&self, kind: SectionKind) -> usize {
+        self.section(kind).map_or(0, |section| {
+            let size = section.size as usize;
+            size.next_multiple_of(self.page_size()) - size
+        })
+    }
+    /// Returns the size of the image in bytes, up to the end of the last section's
+    /// padding.
+    ///
+    /// Anything after this, e.g. an AVB footer or a vendor tag, isn't part of the
+    /// image itself. See [`read_trailing_tag`].
+    #[must_use]
+    pub fn total_size(&self) -> usize {
+        let last = self.sections().pop().expect("every version has a kernel");
+        last.position + self.pad_to_page(last.size as usize)
+    }
+    /// Returns the number of pages a section spans, including its padding.
+    ///
+    /// Returns `None` if the header's version doesn't have the section, or 0 if the
+    /// section is empty.
+    #[must_use]
+    pub fn section_pages(&self, kind: SectionKind) -> Option<usize> {
+        let section = self.section(kind)?;
+        Some((section.size as usize).div_ceil(self.page_size()))
+    }
+    /// Checks that no section is larger than `max` bytes, failing with
+    /// [`Error::SectionTooLarge`] otherwise.
+    ///
+    /// Call this before extracting sections of an untrusted image. See
+    /// [`ParseOptions::max_section_size`].
+    pub fn check_section_sizes(&self, max: u64) -> Result<(), Error> {
+        match self
+            .sections()
+            .into_iter()
+            .find(|section| u64::from(section.size) > max)
+        {
+            Some(section) => Err(Error::SectionTooLarge {
+                kind: section.kind,
+                size: section.size,
+                max,
+            }),
+            None => Ok(()),
+        }
+    }
+    /// Checks that the header is consistent with an image of `image_len` bytes.
+    ///
+    /// For version 4, the boot signature must be non-empty, at most
+    /// [`HeaderV3::MAX_SIGNATURE_SIZE`] bytes and fit within the image. The signature
+    /// always starts on a page boundary.
+    pub fn validate(&self, image_len: u64) -> Result<(), Error> {
+        if let Self::V3(hdr) = self {
+            if let Some(size) = hdr.v4_signature_size {
+                if size == 0 {
+                    return Err(Error::EmptySignature);
+                }
+                if size > HeaderV3::MAX_SIGNATURE_SIZE {
+                    return Err(Error::SignatureTooLarge { size: size.into() });
+                }
+                let end = hdr.bootsig_position() as u64 + size as u64;
+                if end > image_len {
+                    return Err(Error::SignatureOutOfBounds { end, image_len });
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Checks that a version 4 boot signature agrees with the image's AVB footer, which
+    /// is appended when signing the whole partition.
+    ///
+    /// The footer's vbmeta image, of `vbmeta_size` bytes at `vbmeta_offset`, must end
+    /// before the footer in the last [`AvbFooter::SIZE`] bytes of the `image_len`-byte
+    /// image. The signature must end within the original image recorded by the
+    /// footer, before the vbmeta image. A mismatch means the image was re-signed
+    /// inconsistently. The signature checks pass if the header has no boot signature.
+    pub fn check_avb_footer(&self, footer: &AvbFooter, image_len: u64) -> Result<(), Error> {
+        let footer_position = image_len.saturating_sub(AvbFooter::SIZE);
+        let vbmeta_end = footer.vbmeta_offset.saturating_add(footer.vbmeta_size);
+        if vbmeta_end > footer_position {
+            return Err(Error::VbmetaOutOfBounds {
+                end: vbmeta_end,
+                footer_position,
+            });
+        }
+        let Self::V3(hdr) = self else {
+            return Ok(());
+        };
+        let Some(size) = hdr.v4_signature_size else {
+            return Ok(());
+        };
+        let signature_end = hdr.bootsig_position() as u64 + u64::from(size);
+        if signature_end > footer.original_image_size || signature_end > footer.vbmeta_offset {
+            return Err(Error::AvbFooterMismatch {
+                signature_end,
+                original_image_size: footer.original_image_size,
+                vbmeta_offset: footer.vbmeta_offset,
+            });
+        }
+        Ok(())
+    }
+    /// Checks that the header is internally consistent, so that writing it produces a
+    /// well-formed image.
+    ///
+    /// This rejects a page size that isn't a power of two, a command line without a
+    /// null terminator, an empty or oversized v4 boot signature and trailing header
+    /// bytes at version 0, which has no header size field.
+    /// [`ImageWriter::write_header`] calls this first.
+    pub fn validate_for_write(&self) -> Result<(), Error> {
+        match self {
+            Self::V0(hdr) => {
+                if !hdr.page_size.is_power_of_two() {
+                    return Err(Error::InvalidPageSize {
+                        page_size: hdr.page_size,
+                    });
+                }
+                if !hdr.cmdline_part_2.contains(&0) {
+                    return Err(Error::UnterminatedCmdline);
+                }
+                if hdr.versioned == HeaderV0Versioned::V0 && !hdr.trailing.is_empty() {
+                    return Err(Error::UnsupportedHeaderVersion { version: 0 });
+                }
+            }
+            Self::V3(hdr) => {
+                if !hdr.cmdline.contains(&0) {
+                    return Err(Error::UnterminatedCmdline);
+                }
+                if let Some(size) = hdr.v4_signature_size {
+                    if size == 0 {
+                        return Err(Error::EmptySignature);
+                    }
+                    if size > HeaderV3::MAX_SIGNATURE_SIZE {
+                        return Err(Error::SignatureTooLarge { size: size.into() });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Iterates over the sections present in the header's version, like
+/// [`Header::sections`].
+impl IntoIterator for &Header {
+    type Item = Section;
+    type IntoIter = std::vec::IntoIter<Section>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.sections().into_iter()
+    }
+}
+
+/// Boot image header versions this crate can parse and write
+pub const SUPPORTED_BOOT_VERSIONS: std::ops::RangeInclusive<u32> = 0..=4;
+/// Vendor boot image header versions this crate can parse and write
+pub const SUPPORTED_VENDOR_VERSIONS: std::ops::RangeInclusive<u32> = 3..=4;
+
+/// Returns the optional Cargo features this crate was compiled with.
+#[must_use]
+pub fn compiled_features() -> &'static [&'static str] {
+    &[
+        #[cfg(feature = "arbitrary")]
+        "arbitrary",
+        #[cfg(feature = "ffi")]
+        "ffi",
+        #[cfg(feature = "hashing")]
+        "hashing",
+        #[cfg(feature = "serde")]
+        "serde",
+        #[cfg(feature = "zstd")]
+        "zstd",
+    ]
+}
+
+/// Returns the 8-byte magic at `offset` in a reader, without moving the reader.
+///
+/// Fewer bytes are returned if the reader ends first.
+pub fn peek_magic<R: std::io::Read + std::io::Seek>(
+    reader: &mut R,
+    offset: u64,
+) -> std::io::Result<Vec<u8>> {
+    use std::io::Read as _;
+
+    let position = reader.stream_position()?;
+    reader.seek(std::io::SeekFrom::Start(offset))?;
+    let mut magic = Vec::with_capacity(8);
+    (&mut *reader).take(8).read_to_end(&mut magic)?;
+    reader.seek(std::io::SeekFrom::Start(position))?;
+    Ok(magic)
+}
+
+/// Returns how many bytes of `bytes` match the boot image magic `ANDROID!` at the
+/// same position.
+///
+/// [`Header::parse`] fails with [`Error::CorruptedMagic`] if 6 or 7 bytes match.
+#[must_use]
+pub fn magic_similarity(bytes: &[u8; 8]) -> u8 {
+    bytes
+        .iter()
+        .zip(b"ANDROID!")
+        .filter(|(a, b)| a == b)
+        .count() as u8
+}
+
+/// Checks that the header size field of a boot image agrees with its header version,
+/// without parsing the rest of the header.
+///
+/// This is a quick pre-check, e.g. for triaging many images. Version 0 has no header
+/// size field, so it always passes. The magic isn't checked.
+pub fn check_header_size<R: std::io::Read + std::io::Seek>(reader: &mut R) -> Result<(), Error> {
+    let version = read_u32_at(reader, 0x28)?;
+    if version == 0 {
+        return Ok(());
+    }
+    let (expected, offset) =
+        header_size_field(version).ok_or(Error::UnknownHeaderVersion { version })?;
+    let actual = read_u32_at(reader, offset)?;
+    if actual != expected {
+        return Err(Error::HeaderSizeMismatch {
+            version,
+            expected,
+            actual,
+        });
+    }
+    Ok(())
 }
 
-// TODO: unit tests
+/// Reads only the OS version and patch level of a boot image, without parsing the rest
+/// of the header.
+///
+/// This is a fast path for scanning many images. The magic isn't checked.
+pub fn read_osversionpatch<R: std::io::Read + std::io::Seek>(
+    reader: &mut R,
+) -> Result<OsVersionPatch, Error> {
+    let offset = match read_u32_at(reader, 0x28)? {
+        0..=2 => 0x2c,
+        3 | 4 => 16,
+        version => return Err(Error::UnknownHeaderVersion { version }),
+    };
+    Ok(OsVersionPatch(read_u32_at(reader, offset)?))
+}
+
+/// Returns the known header size of a header version and the offset of its header
+/// size field, or `None` for version 0, which has no such field, and unknown
+/// versions.
+fn header_size_field(version: u32) -> Option<(u32, u64)> {
+    match version {
+        1 => Some((1648, 1644)),
+        2 => Some((1660, 1644)),
+        3 => Some((1580, 20)),
+        4 => Some((1584, 20)),
+        _ => None,
+    }
+}
+
+/// Reads a little-endian `u32` at `offset`.
+fn read_u32_at<R: std::io::Read + std::io::Seek>(
+    reader: &mut R,
+    offset: u64,
+) -> Result<u32, Error> {
+    let mut buf = [0; 4];
+    reader.seek(std::io::SeekFrom::Start(offset))?;
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+/// Returns the offset of the first `ANDROID!` magic in a reader.
+///
+/// Pass the offset to [`Header::parse_at`] to parse an image with a prepended header.
+pub fn find_android_magic<R: std::io::Read + std::io::Seek>(
+    reader: &mut R,
+) -> std::io::Result<Option<u64>> {
+    const MAGIC: &[u8; 8] = b"ANDROID!";
+    reader.seek(std::io::SeekFrom::Start(0))?;
+
+    let mut buf = vec![0; 0x10000];
+    // Offset of buf[0] in the reader
+    let mut base = 0;
+    // Bytes kept from the previous read, so magics spanning two reads are found
+    let mut kept = 0;
+    loop {
+        let n = match reader.read(&mut buf[kept..]) {
+            Ok(0) => return Ok(None),
+            Ok(n) => n,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        let len = kept + n;
+        if let Some(idx) = buf[..len].windows(MAGIC.len()).position(|w| w == MAGIC) {
+            return Ok(Some(base + idx as u64));
+        }
+        kept = len.min(MAGIC.len() - 1);
+        buf.copy_within(len - kept..len, 0);
+        base += (len - kept) as u64;
+    }
+}
+
+/// Splits a kernel command line into the base and extra command line fields of a
+/// version 0-2 header, like [`HeaderV0::set_cmdline`].
+///
+/// The first 511 bytes go to the base command line and the rest to the extra command
+/// line, each null-terminated.
+#[allow(clippy::type_complexity)]
+pub fn split_cmdline(cmdline: &[u8]) -> Result<(Box<[u8; 512]>, Box<[u8; 1024]>), CmdlineTooLong> {
+    const MAX: usize = 511 + 1023;
+    if cmdline.len() > MAX {
+        return Err(CmdlineTooLong {
+            len: cmdline.len(),
+            max: MAX,
+        });
+    }
+    let (base, extra) = cmdline.split_at(cmdline.len().min(511));
+    let mut base_field = Box::new([0; 512]);
+    base_field[..base.len()].copy_from_slice(base);
+    let mut extra_field = Box::new([0; 1024]);
+    extra_field[..extra.len()].copy_from_slice(extra);
+    Ok((base_field, extra_field))
+}
+
+fn zero_after_null(buf: &mut [u8]) {
+    if let Some(null_idx) = buf.iter().position(|x| *x == 0) {
+        buf[null_idx..].fill(0);
+    }
+}
+
+fn take_until_null(input: &[u8]) -> &[u8] {
+    match input.iter().position(|x| *x == 0) {
+        Some(null_idx) => &input[..null_idx],
+        None => input,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub(crate) fn header_v0(versioned: HeaderV0Versioned) -> HeaderV0 {
+        HeaderV0 {
+            kernel_size: 0,
+            kernel_addr: 0x10008000,
+            ramdisk_size: 0,
+            ramdisk_addr: 0x11000000,
+            second_bootloader_size: 0,
+            second_bootloader_addr: 0x10f00000,
+            tags_addr: 0x10000100,
+            page_size: 2048,
+            osversionpatch: OsVersionPatch(0),
+            board_name: [0; 16],
+            cmdline_part_1: Box::new([0; 512]),
+            hash_digest: [0; 32],
+            cmdline_part_2: Box::new([0; 1024]),
+            versioned,
+            trailing: Vec::new(),
+        }
+    }
+
+    pub(crate) fn header_v3(kernel_size: u32, ramdisk_size: u32) -> HeaderV3 {
+        HeaderV3 {
+            kernel_size,
+            ramdisk_size,
+            osversionpatch: OsVersionPatch(0),
+            cmdline: Box::new([0; 1536]),
+            v4_signature_size: None,
+            trailing: Vec::new(),
+        }
+    }
+
+    /// Builds an image like `mkbootimg`, filling each section with its kind's index + 1.
+    pub(crate) fn build_image(hdr: &Header) -> Vec<u8> {
+        let mut image = Vec::new();
+        hdr.write(&mut image).unwrap();
+        for section in hdr.sections() {
+            image.resize(section.position, 0);
+            image.resize(image.len() + section.size as usize, section.kind as u8 + 1);
+        }
+        image.resize(image.len().next_multiple_of(hdr.page_size()), 0);
+        image
+    }
+
+    #[test]
+    fn kernel_position() {
+        let mut v0 = header_v0(HeaderV0Versioned::V0);
+        v0.kernel_size = 3000;
+        v0.ramdisk_size = 10;
+        let mut v1 = header_v0(HeaderV0Versioned::V1 {
+            recovery_dtbo_size: 0,
+            recovery_dtbo_addr: 0,
+        });
+        v1.page_size = 4096;
+        v1.kernel_size = 3000;
+        v1.ramdisk_size = 10;
+        // With small pages, padding the v2 header size of 1660 bytes would give 1664
+        // for every version
+        let mut small_v0 = v0.clone();
+        small_v0.page_size = 32;
+        let mut small_v1 = v1.clone();
+        small_v1.page_size = 16;
+
+        for (hdr, kernel_position) in [(v0, 2048), (v1, 4096), (small_v0, 1632), (small_v1, 1648)] {
+            let image = build_image(&Header::V0(hdr));
+            let hdr = Header::parse(&mut std::io::Cursor::new(&image)).unwrap();
+            assert_eq!(hdr.kernel_position(), kernel_position);
+            assert_eq!(image[kernel_position - 1], 0);
+            assert_eq!(image[kernel_position], SectionKind::Kernel as u8 + 1);
+            assert_eq!(
+                image[hdr.ramdisk_position()..][..10],
+                [SectionKind::Ramdisk as u8 + 1; 10]
+            );
+        }
+    }
+
+    #[test]
+    fn kernel_on_first_page_boundary() {
+        let versioned = [
+            HeaderV0Versioned::V0,
+            HeaderV0Versioned::V1 {
+                recovery_dtbo_size: 0,
+                recovery_dtbo_addr: 0,
+            },
+            HeaderV0Versioned::V2 {
+                recovery_dtbo_size: 0,
+                recovery_dtbo_addr: 0,
+                dtb_size: 0,
+                dtb_addr: 0,
+            },
+        ];
+        let mut headers = Vec::new();
+        for versioned in versioned {
+            for page_size in [2048, 4096, 8192, 16384] {
+                let mut hdr = header_v0(versioned);
+                hdr.page_size = page_size;
+                headers.push(Header::V0(hdr));
+            }
+        }
+        let mut v4 = header_v3(0, 0);
+        v4.v4_signature_size = Some(4096);
+        headers.extend([Header::V3(header_v3(0, 0)), Header::V3(v4)]);
+
+        for hdr in headers {
+            let position = hdr.kernel_position();
+            assert!(position >= hdr.header_len(), "{hdr:?}");
+            assert_eq!(position % hdr.page_size(), 0, "{hdr:?}");
+            assert_eq!(position, hdr.page_size(), "{hdr:?}");
+        }
+    }
+
+    #[test]
+    fn empty_sections() {
+        let mut hdr = header_v0(HeaderV0Versioned::V2 {
+            recovery_dtbo_size: 0,
+            recovery_dtbo_addr: 0,
+            dtb_size: 0,
+            dtb_addr: 0,
+        });
+        hdr.kernel_size = 1;
+        hdr.ramdisk_size = 1;
+        assert_eq!(hdr.recovery_dtbo_position(), Some(3 * 2048));
+        assert_eq!(hdr.dtb_position(), Some(3 * 2048));
+        assert_eq!(
+            hdr.sections().last(),
+            Some(&Section {
+                kind: SectionKind::Dtb,
+                position: 3 * 2048,
+                size: 0,
+            })
+        );
+
+        hdr.versioned = HeaderV0Versioned::V1 {
+            recovery_dtbo_size: 0,
+            recovery_dtbo_addr: 0,
+        };
+        assert_eq!(hdr.recovery_dtbo_position(), Some(3 * 2048));
+        assert_eq!(hdr.dtb_position(), None);
+
+        hdr.versioned = HeaderV0Versioned::V0;
+        assert_eq!(hdr.recovery_dtbo_position(), None);
+    }
+
+    #[test]
+    fn section_range() {
+        let mut hdr = header_v0(HeaderV0Versioned::V1 {
+            recovery_dtbo_size: 10,
+            recovery_dtbo_addr: 0,
+        });
+        hdr.kernel_size = 3000;
+        hdr.ramdisk_size = 100;
+        let hdr = Header::V0(hdr);
+        assert_eq!(hdr.section_range(SectionKind::Kernel), Some(2048..5048));
+        assert_eq!(
+            hdr.section_range(SectionKind::Ramdisk),
+            Some(hdr.ramdisk_position() as u64..hdr.ramdisk_position() as u64 + 100)
+        );
+        assert_eq!(hdr.section_range(SectionKind::Second), Some(8192..8192));
+        assert_eq!(
+            hdr.section_range(SectionKind::RecoveryDtbo),
+            Some(8192..8202)
+        );
+        assert_eq!(hdr.section_range(SectionKind::Dtb), None);
+        assert_eq!(hdr.section_range(SectionKind::BootSignature), None);
+    }
+
+    #[test]
+    fn sector_sizes() {
+        let mut hdr = header_v0(HeaderV0Versioned::V0);
+        hdr.kernel_size = 3;
+        hdr.ramdisk_size = 1;
+        let image = build_image(&Header::V0(hdr));
+
+        let options = ParseOptions {
+            size_unit: SizeUnit::Sectors,
+            ..Default::default()
+        };
+        let hdr = Header::parse_with_options(&mut std::io::Cursor::new(&image), &options).unwrap();
+        assert_eq!(hdr.kernel_size(), 1536);
+        assert_eq!(hdr.ramdisk_size(), 512);
+        assert_eq!(hdr.ramdisk_position(), 4096);
+
+        let hdr =
+            Header::parse_with_options(&mut std::io::Cursor::new(&image), &ParseOptions::default())
+                .unwrap();
+        assert_eq!(hdr.kernel_size(), 3);
+    }
+
+    #[test]
+    fn max_section_size() {
+        let mut hdr = header_v0(HeaderV0Versioned::V0);
+        hdr.kernel_size = 100;
+        hdr.ramdisk_size = 0xf000_0000;
+        let mut image = Vec::new();
+        Header::V0(hdr).write(&mut image).unwrap();
+        image.resize(2048, 0);
+
+        let options = ParseOptions {
+            max_section_size: Some(1024),
+            ..Default::default()
+        };
+        assert!(matches!(
+            Header::parse_with_options(&mut std::io::Cursor::new(&image), &options),
+            Err(Error::SectionTooLarge {
+                kind: SectionKind::Ramdisk,
+                size: 0xf000_0000,
+                max: 1024
+            })
+        ));
+        assert!(matches!(
+            Header::parse_with_options(&mut std::io::Cursor::new(&image), &ParseOptions::default()),
+            Err(Error::SectionTooLarge { .. })
+        ));
+        let options = ParseOptions {
+            max_section_size: None,
+            ..Default::default()
+        };
+        Header::parse_with_options(&mut std::io::Cursor::new(&image), &options).unwrap();
+    }
+
+    #[test]
+    fn u64_addresses_little_endian() {
+        let hdr = Header::V0(header_v0(HeaderV0Versioned::V2 {
+            recovery_dtbo_size: 0,
+            recovery_dtbo_addr: 0xfedcba9876543210,
+            dtb_size: 0,
+            dtb_addr: 0x0123456789abcdef,
+        }));
+        let mut buf = Vec::new();
+        hdr.write(&mut buf).unwrap();
+        assert_eq!(
+            buf[1636..1644],
+            [0x10, 0x32, 0x54, 0x76, 0x98, 0xba, 0xdc, 0xfe]
+        );
+        assert_eq!(
+            buf[1652..1660],
+            [0xef, 0xcd, 0xab, 0x89, 0x67, 0x45, 0x23, 0x01]
+        );
+        assert_eq!(Header::parse(&mut std::io::Cursor::new(&buf)).unwrap(), hdr);
+    }
+
+    #[test]
+    fn check_header_size() {
+        let check = |image: &[u8]| super::check_header_size(&mut std::io::Cursor::new(image));
+        let v2 = header_v0(HeaderV0Versioned::V2 {
+            recovery_dtbo_size: 0,
+            recovery_dtbo_addr: 0,
+            dtb_size: 0,
+            dtb_addr: 0,
+        });
+        let mut v2 = build_image(&Header::V0(v2));
+        check(&v2).unwrap();
+        check(&build_image(&Header::V0(header_v0(HeaderV0Versioned::V0)))).unwrap();
+        check(&build_image(&Header::V3(header_v3(0, 0)))).unwrap();
+
+        v2[1644..1648].copy_from_slice(&1648u32.to_le_bytes());
+        assert!(matches!(
+            check(&v2),
+            Err(Error::HeaderSizeMismatch {
+                version: 2,
+                expected: 1660,
+                actual: 1648
+            })
+        ));
+        v2[0x28] = 7;
+        assert!(matches!(
+            check(&v2),
+            Err(Error::UnknownHeaderVersion { version: 7 })
+        ));
+    }
+
+    #[test]
+    fn sections_through_bufreader() {
+        use std::io::{BufReader, Read, Seek, SeekFrom};
+
+        let mut hdr = header_v0(HeaderV0Versioned::V0);
+        hdr.kernel_size = 3000;
+        hdr.ramdisk_size = 100;
+        let image = build_image(&Header::V0(hdr));
+
+        // Smaller than the header, so the buffer is refilled while parsing
+        let mut r = BufReader::with_capacity(512, std::io::Cursor::new(&image));
+        let hdr = Header::parse(&mut r).unwrap();
+        let mut ramdisk = Vec::new();
+        r.seek(SeekFrom::Start(hdr.ramdisk_position() as u64))
+            .unwrap();
+        (&mut r)
+            .take(hdr.ramdisk_size() as u64)
+            .read_to_end(&mut ramdisk)
+            .unwrap();
+        assert_eq!(ramdisk, [SectionKind::Ramdisk as u8 + 1; 100]);
+
+        let extracted = hdr.extract_all(&mut r).unwrap();
+        assert_eq!(extracted.kernel, [SectionKind::Kernel as u8 + 1; 3000]);
+        assert_eq!(extracted.ramdisk, ramdisk);
+    }
+
+    #[test]
+    fn iterate_sections() {
+        let hdr = Header::V3(header_v3(100, 10));
+        let mut sections = Vec::new();
+        for section in &hdr {
+            sections.push(section);
+        }
+        assert_eq!(sections, hdr.sections());
+        assert_eq!(
+            sections.iter().map(|s| s.kind).collect::<Vec<_>>(),
+            [SectionKind::Kernel, SectionKind::Ramdisk]
+        );
+    }
+
+    #[test]
+    fn section_pages() {
+        let mut hdr = header_v3(4096, 4097);
+        hdr.v4_signature_size = Some(0);
+        let hdr = Header::V3(hdr);
+        assert_eq!(hdr.section_pages(SectionKind::Kernel), Some(1));
+        assert_eq!(hdr.section_pages(SectionKind::Ramdisk), Some(2));
+        assert_eq!(hdr.section_pages(SectionKind::BootSignature), Some(0));
+        assert_eq!(hdr.section_pages(SectionKind::Second), None);
+    }
+
+    #[test]
+    fn pad_to_page() {
+        let v0 = Header::V0(header_v0(HeaderV0Versioned::V0));
+        assert_eq!(v0.pad_to_page(0), 0);
+        assert_eq!(v0.pad_to_page(4096), 4096);
+        assert_eq!(v0.pad_to_page(2049), 4096);
+        let v3 = Header::V3(header_v3(0, 0));
+        assert_eq!(v3.pad_to_page(4096), 4096);
+        assert_eq!(v3.pad_to_page(4097), 8192);
+        assert_eq!(HeaderV3::pad_to_page(1), 4096);
+    }
+
+    #[test]
+    fn v1_header_size() {
+        let hdr = Header::V0(header_v0(HeaderV0Versioned::V1 {
+            recovery_dtbo_size: 0,
+            recovery_dtbo_addr: 0,
+        }));
+        let mut image = build_image(&hdr);
+        assert_eq!(image[1644..1648], 1648u32.to_le_bytes());
+        assert_eq!(
+            Header::parse(&mut std::io::Cursor::new(&image)).unwrap(),
+            hdr
+        );
+
+        image[1644..1648].copy_from_slice(&1632u32.to_le_bytes());
+        let err = Header::parse(&mut std::io::Cursor::new(&image)).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::HeaderSizeMismatch {
+                version: 1,
+                expected: 1648,
+                actual: 1632
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "header size is 1632, but header version 1 has 1648 (the size of version 0)"
+        );
+    }
+
+    #[test]
+    fn read_osversionpatch() {
+        let osversionpatch = OsVersionPatch::new(OsVersion::new(14, 0, 0), OsPatch::new(2024, 6));
+        let mut v2 = header_v0(HeaderV0Versioned::V2 {
+            recovery_dtbo_size: 0,
+            recovery_dtbo_addr: 0,
+            dtb_size: 0,
+            dtb_addr: 0,
+        });
+        v2.osversionpatch = osversionpatch;
+        let mut v4 = header_v3(0, 0);
+        v4.osversionpatch = osversionpatch;
+        v4.v4_signature_size = Some(4096);
+
+        for hdr in [Header::V0(v2), Header::V3(v4)] {
+            let image = build_image(&hdr);
+            let parsed = Header::parse(&mut std::io::Cursor::new(&image)).unwrap();
+            assert_eq!(
+                super::read_osversionpatch(&mut std::io::Cursor::new(&image)).unwrap(),
+                parsed.osversionpatch()
+            );
+            assert_eq!(parsed.osversionpatch(), osversionpatch);
+        }
+    }
+
+    #[test]
+    fn allow_newer() {
+        let allow_newer = ParseOptions {
+            allow_newer: true,
+            ..Default::default()
+        };
+        let mut v2 = header_v0(HeaderV0Versioned::V2 {
+            recovery_dtbo_size: 0,
+            recovery_dtbo_addr: 0,
+            dtb_size: 0,
+            dtb_addr: 0,
+        });
+        v2.kernel_size = 100;
+        let mut v4 = header_v3(100, 0);
+        v4.v4_signature_size = Some(4096);
+
+        for (hdr, size_offset) in [(Header::V0(v2), 1644), (Header::V3(v4), 20)] {
+            let mut image = build_image(&hdr);
+            let known = hdr.header_len();
+            let header_size = known as u32 + 8;
+            image[size_offset..][..4].copy_from_slice(&header_size.to_le_bytes());
+            image[known..][..8].copy_from_slice(b"newfield");
+
+            assert!(Header::parse(&mut std::io::Cursor::new(&image)).is_err());
+            let parsed =
+                Header::parse_with_options(&mut std::io::Cursor::new(&image), &allow_newer)
+                    .unwrap();
+            assert_eq!(parsed.header_len(), known + 8);
+            assert_eq!(parsed.kernel_position(), hdr.kernel_position());
+            match &parsed {
+                Header::V0(hdr) => assert_eq!(hdr.trailing, b"newfield"),
+                Header::V3(hdr) => assert_eq!(hdr.trailing, b"newfield"),
+            }
+
+            let mut written = Vec::new();
+            parsed.write(&mut written).unwrap();
+            assert_eq!(written, image[..known + 8]);
+
+            let hdr = Header::parse_with_options(
+                &mut std::io::Cursor::new(build_image(&hdr)),
+                &allow_newer,
+            )
+            .unwrap();
+            assert_eq!(hdr.header_len(), known);
+        }
+
+        // A v3/v4 header larger than a page would overlap the kernel
+        let mut image = build_image(&Header::V3(header_v3(100, 0)));
+        image[20..][..4].copy_from_slice(&4097u32.to_le_bytes());
+        assert!(matches!(
+            Header::parse_with_options(&mut std::io::Cursor::new(&image), &allow_newer),
+            Err(Error::HeaderSizeMismatch {
+                version: 3,
+                expected: 1580,
+                actual: 4097
+            })
+        ));
+    }
+
+    #[test]
+    fn too_small() {
+        let mut image = b"ANDROID!".to_vec();
+        image.resize(100, 0);
+        assert!(matches!(
+            Header::parse(&mut std::io::Cursor::new(&image)),
+            Err(Error::TooSmall {
+                need: 1632,
+                have: 100
+            })
+        ));
+        assert!(matches!(
+            Header::parse(&mut std::io::Cursor::new(&image[..20])),
+            Err(Error::TooSmall { have: 20, .. })
+        ));
+    }
+
+    #[test]
+    fn compiled_features() {
+        let features = super::compiled_features();
+        for (feature, enabled) in [
+            ("arbitrary", cfg!(feature = "arbitrary")),
+            ("ffi", cfg!(feature = "ffi")),
+            ("hashing", cfg!(feature = "hashing")),
+            ("serde", cfg!(feature = "serde")),
+            ("zstd", cfg!(feature = "zstd")),
+        ] {
+            assert_eq!(features.contains(&feature), enabled, "{feature}");
+        }
+    }
+
+    #[test]
+    fn corrupted_magic() {
+        assert_eq!(magic_similarity(b"ANDROID!"), 8);
+        assert_eq!(magic_similarity(b"ANDROIE!"), 7);
+        assert_eq!(magic_similarity(b"android!"), 1);
+
+        let mut image = build_image(&Header::V0(header_v0(HeaderV0Versioned::V0)));
+        image[6] = b'E';
+        let err = Header::parse(&mut std::io::Cursor::new(&image)).unwrap_err();
+        assert!(matches!(err, Error::CorruptedMagic { matching: 7 }));
+        assert_eq!(
+            err.to_string(),
+            "magic looks corrupted (7/8 bytes match ANDROID!)"
+        );
+
+        image[..8].copy_from_slice(b"android!");
+        assert!(matches!(
+            Header::parse(&mut std::io::Cursor::new(&image)),
+            Err(Error::Binrw(_))
+        ));
+    }
+
+    #[test]
+    fn foreign_format() {
+        let parse = |data: &[u8]| {
+            let mut image = data.to_vec();
+            image.resize(4096, 0);
+            Header::parse(&mut std::io::Cursor::new(image))
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            parse(b"\x7fELF\x02\x01\x01"),
+            "this is an ELF file, likely a bare kernel or bootloader; pack it with `mkbootimg`"
+        );
+        assert_eq!(
+            parse(&[0x1f, 0x8b, 8, 0]),
+            "this is gzip-compressed; decompress it first, e.g. with `gunzip`"
+        );
+
+        let mut r = std::io::Cursor::new(b"CHROMEOS....");
+        r.set_position(3);
+        assert_eq!(peek_magic(&mut r, 0).unwrap(), b"CHROMEOS");
+        assert_eq!(r.position(), 3);
+        assert_eq!(peek_magic(&mut r, 10).unwrap(), b"..");
+    }
+
+    #[test]
+    fn prepended_header() {
+        let mut hdr = header_v0(HeaderV0Versioned::V0);
+        hdr.kernel_size = 100;
+        let hdr = Header::V0(hdr);
+        let mut image = vec![0xaa; 256];
+        image.extend(build_image(&hdr));
+
+        let mut r = std::io::Cursor::new(&image);
+        assert_eq!(find_android_magic(&mut r).unwrap(), Some(256));
+        assert_eq!(Header::parse_at(&mut r, 256).unwrap(), hdr);
+        assert!(Header::parse(&mut r).is_err());
+
+        assert_eq!(
+            find_android_magic(&mut std::io::Cursor::new([0; 1000])).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn invalid_page_size() {
+        for page_size in [0, 3000] {
+            let mut hdr = header_v0(HeaderV0Versioned::V0);
+            hdr.page_size = page_size;
+            let mut image = Vec::new();
+            Header::V0(hdr).write(&mut image).unwrap();
+            image.resize(4096, 0);
+            assert!(matches!(
+                Header::parse(&mut std::io::Cursor::new(&image)),
+                Err(Error::InvalidPageSize { page_size: p }) if p == page_size
+            ));
+        }
+    }
+
+    #[test]
+    fn warnings() {
+        let parse = |image: &[u8]| {
+            Header::parse_with_warnings(&mut std::io::Cursor::new(image))
+                .unwrap()
+                .1
+        };
+        let mut v0 = header_v0(HeaderV0Versioned::V0);
+        assert_eq!(parse(&build_image(&Header::V0(v0.clone()))), []);
+
+        v0.board_name = *b"abcdefghijklmnop";
+        v0.cmdline_part_2.fill(b'a');
+        v0.page_size = 65536;
+        assert_eq!(
+            parse(&build_image(&Header::V0(v0))),
+            [
+                Warning::UnterminatedCmdline,
+                Warning::UnterminatedBoardName,
+                Warning::UnusualPageSize { page_size: 65536 }
+            ]
+        );
+
+        let mut v1 = header_v0(HeaderV0Versioned::V1 {
+            recovery_dtbo_size: 0,
+            recovery_dtbo_addr: 0,
+        });
+        assert_eq!(parse(&build_image(&Header::V0(v1.clone()))), []);
+        v1.versioned = HeaderV0Versioned::V1 {
+            recovery_dtbo_size: 0,
+            recovery_dtbo_addr: 0x11f00000,
+        };
+        assert_eq!(
+            parse(&build_image(&Header::V0(v1))),
+            [Warning::InconsistentRecoveryDtbo {
+                size: 0,
+                addr: 0x11f00000
+            }]
+        );
+
+        let mut v3 = header_v3(0, 0);
+        v3.cmdline.fill(b'a');
+        let mut image = build_image(&Header::V3(v3));
+        image[30] = 1;
+        assert_eq!(
+            parse(&image),
+            [Warning::UnterminatedCmdline, Warning::NonZeroReserved]
+        );
+    }
+
+    #[test]
+    fn cmdline_halves() {
+        let mut hdr = header_v0(HeaderV0Versioned::V0);
+        hdr.cmdline_part_1[..7].copy_from_slice(b"console");
+        hdr.cmdline_part_1[8..12].copy_from_slice(b"junk");
+        hdr.cmdline_part_2[..6].copy_from_slice(b" quiet");
+        assert_eq!(hdr.base_cmdline(), b"console");
+        assert_eq!(hdr.extra_cmdline(), b" quiet");
+        assert_eq!(hdr.cmdline(), b"console quiet");
+        assert_eq!(Header::V0(hdr).cmdline(), b"console quiet");
+    }
+
+    #[test]
+    fn cmdline_used_len() {
+        let mut hdr = header_v0(HeaderV0Versioned::V0);
+        assert_eq!(hdr.cmdline_capacity(), 1536);
+        assert_eq!(hdr.cmdline_used_len(), 0);
+        hdr.set_cmdline(&[b'a'; 600]).unwrap();
+        assert_eq!(hdr.cmdline_used_len(), 600);
+        hdr.cmdline_part_1.fill(b'a');
+        hdr.cmdline_part_2.fill(b'a');
+        assert_eq!(hdr.cmdline_used_len(), 1536);
+    }
+
+    #[test]
+    fn set_cmdline() {
+        let mut hdr = header_v0(HeaderV0Versioned::V0);
+        let cmdline = [b'a'; 600];
+        hdr.set_cmdline(&cmdline).unwrap();
+        assert_eq!(hdr.base_cmdline(), &cmdline[..511]);
+        assert_eq!(hdr.extra_cmdline(), &cmdline[511..]);
+        assert_eq!(hdr.cmdline(), cmdline);
+        assert_eq!(
+            hdr.set_cmdline(&[b'a'; 1535]),
+            Err(CmdlineTooLong {
+                len: 1535,
+                max: 1534
+            })
+        );
+
+        let mut hdr = header_v3(0, 0);
+        hdr.set_cmdline(&[b'a'; 1535]).unwrap();
+        assert!(hdr.set_cmdline(&[b'a'; 1536]).is_err());
+    }
+
+    #[test]
+    fn split_cmdline() {
+        let cmdline: Vec<u8> = (0..600).map(|i| b'a' + (i % 26) as u8).collect();
+        let (base, extra) = super::split_cmdline(&cmdline).unwrap();
+        assert_eq!(base[..511], cmdline[..511]);
+        assert_eq!(base[511], 0);
+        assert_eq!(extra[..89], cmdline[511..]);
+        assert_eq!(extra[89..], [0; 935]);
+
+        let mut hdr = header_v0(HeaderV0Versioned::V0);
+        hdr.set_cmdline(&cmdline).unwrap();
+        assert_eq!((hdr.cmdline_part_1, hdr.cmdline_part_2), (base, extra));
+        assert!(super::split_cmdline(&[b'a'; 1535]).is_err());
+    }
+
+    #[test]
+    fn with_fields() {
+        let hdr = Header::V0(header_v0(HeaderV0Versioned::V0));
+        let patched = hdr
+            .with_cmdline(b"quiet")
+            .unwrap()
+            .with_os_version(OsVersion::new(13, 0, 0))
+            .unwrap()
+            .with_os_patch(OsPatch::new(2024, 12))
+            .unwrap()
+            .with_page_size(4096)
+            .unwrap();
+        assert_eq!(hdr, Header::V0(header_v0(HeaderV0Versioned::V0)));
+        assert_eq!(patched.cmdline(), b"quiet");
+        assert_eq!(
+            patched.osversionpatch(),
+            OsVersionPatch::new(OsVersion::new(13, 0, 0), OsPatch::new(2024, 12))
+        );
+        assert_eq!(patched.page_size(), 4096);
+
+        assert!(hdr.with_page_size(3000).is_err());
+        let v3 = Header::V3(header_v3(0, 0));
+        assert_eq!(v3.with_page_size(4096).unwrap(), v3);
+        assert!(matches!(
+            v3.with_page_size(2048),
+            Err(Error::InvalidPageSize { page_size: 2048 })
+        ));
+        assert!(hdr.with_os_patch(OsPatch::new(2024, 13)).is_err());
+    }
+
+    #[test]
+    fn set_version() {
+        let mut hdr = header_v0(HeaderV0Versioned::V0);
+        hdr.set_version(2).unwrap();
+        assert_eq!(
+            hdr.versioned,
+            HeaderV0Versioned::V2 {
+                recovery_dtbo_size: 0,
+                recovery_dtbo_addr: 0,
+                dtb_size: 0,
+                dtb_addr: 0,
+            }
+        );
+        hdr.set_version(0).unwrap();
+        assert_eq!(hdr, header_v0(HeaderV0Versioned::V0));
+
+        hdr.set_version(2).unwrap();
+        if let HeaderV0Versioned::V2 { dtb_size, .. } = &mut hdr.versioned {
+            *dtb_size = 100;
+        }
+        let v2 = hdr.clone();
+        assert!(matches!(
+            hdr.set_version(1),
+            Err(Error::LossyDowngrade { version: 1 })
+        ));
+        assert_eq!(hdr, v2);
+        for version in [3, 4] {
+            assert!(matches!(
+                hdr.set_version(version),
+                Err(Error::UnsupportedHeaderVersion { version: v }) if v == version
+            ));
+        }
+        assert!(matches!(
+            hdr.set_version(5),
+            Err(Error::UnknownHeaderVersion { version: 5 })
+        ));
+        assert_eq!(hdr, v2);
+    }
+
+    #[test]
+    fn header_len() {
+        let v1 = HeaderV0Versioned::V1 {
+            recovery_dtbo_size: 0,
+            recovery_dtbo_addr: 0,
+        };
+        let v2 = HeaderV0Versioned::V2 {
+            recovery_dtbo_size: 0,
+            recovery_dtbo_addr: 0,
+            dtb_size: 0,
+            dtb_addr: 0,
+        };
+        let mut v4 = header_v3(0, 0);
+        v4.v4_signature_size = Some(0);
+        let headers = [
+            (Header::V0(header_v0(HeaderV0Versioned::V0)), 1632),
+            (Header::V0(header_v0(v1)), 1648),
+            (Header::V0(header_v0(v2)), 1660),
+            (Header::V3(header_v3(0, 0)), 1580),
+            (Header::V3(v4), 1584),
+        ];
+        for (hdr, len) in headers {
+            assert_eq!(hdr.header_len(), len);
+            let mut buf = Vec::new();
+            hdr.write(&mut buf).unwrap();
+            assert_eq!(buf.len(), len);
+        }
+    }
+
+    #[test]
+    fn parse_with_len() {
+        let mut v4 = header_v3(3000, 10);
+        v4.v4_signature_size = Some(0);
+        for hdr in [Header::V0(header_v0(HeaderV0Versioned::V0)), Header::V3(v4)] {
+            let image = build_image(&hdr);
+            let (parsed, len) = Header::parse_with_len(&mut std::io::Cursor::new(&image)).unwrap();
+            assert_eq!(parsed, hdr);
+            assert_eq!(len, hdr.header_len());
+        }
+    }
+
+    #[test]
+    fn expected_header_size() {
+        let v2 = HeaderV0Versioned::V2 {
+            recovery_dtbo_size: 0,
+            recovery_dtbo_addr: 0,
+            dtb_size: 0,
+            dtb_addr: 0,
+        };
+        for (version, size) in [(0, 1632), (1, 1648), (2, 1660)] {
+            let mut hdr = header_v0(v2);
+            hdr.set_version(version).unwrap();
+            assert_eq!(Header::V0(hdr).expected_header_size(), size);
+        }
+        let mut v4 = header_v3(0, 0);
+        v4.v4_signature_size = Some(0);
+        assert_eq!(Header::V3(header_v3(0, 0)).expected_header_size(), 1580);
+        assert_eq!(Header::V3(v4.clone()).expected_header_size(), 1584);
+
+        v4.trailing = vec![0; 8];
+        let hdr = Header::V3(v4);
+        assert_eq!(hdr.expected_header_size(), 1584);
+        assert_eq!(hdr.header_len(), 1592);
+    }
+
+    #[test]
+    fn layout() {
+        let hdr = Header::V3(header_v3(5000, 4096));
+        assert_eq!(
+            hdr.layout(),
+            [
+                Region {
+                    name: "header",
+                    offset: 0,
+                    size: 1580,
+                    padding: 4096 - 1580,
+                },
+                Region {
+                    name: "kernel",
+                    offset: 4096,
+                    size: 5000,
+                    padding: 8192 - 5000,
+                },
+                Region {
+                    name: "ramdisk",
+                    offset: 3 * 4096,
+                    size: 4096,
+                    padding: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn sections_v3() {
+        let mut hdr = header_v3(5000, 100);
+        let kinds = |hdr: &HeaderV3| {
+            hdr.sections()
+                .iter()
+                .map(|section| section.kind)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(kinds(&hdr), [SectionKind::Kernel, SectionKind::Ramdisk]);
+
+        hdr.v4_signature_size = Some(4096);
+        assert_eq!(
+            kinds(&hdr),
+            [
+                SectionKind::Kernel,
+                SectionKind::Ramdisk,
+                SectionKind::BootSignature
+            ]
+        );
+        assert_eq!(
+            hdr.sections()[2],
+            Section {
+                kind: SectionKind::BootSignature,
+                position: hdr.bootsig_position(),
+                size: 4096,
+            }
+        );
+    }
+
+    #[test]
+    fn normalize() {
+        let mut a = header_v0(HeaderV0Versioned::V0);
+        a.set_cmdline(&[b'a'; 600]).unwrap();
+        a.board_name[..4].copy_from_slice(b"foo\0");
+        let mut b = a.clone();
+        a.board_name[5] = 0xff;
+        a.cmdline_part_2[100] = 0xff;
+        b.cmdline_part_2[200] = 0xee;
+        b.board_name[15] = 0xee;
+
+        let write = |hdr: HeaderV0| {
+            let mut hdr = Header::V0(hdr);
+            hdr.normalize();
+            let mut buf = Vec::new();
+            hdr.write(&mut buf).unwrap();
+            buf
+        };
+        assert_ne!(a, b);
+        assert_eq!(write(a.clone()), write(b));
+
+        let mut hdr = Header::V0(a);
+        hdr.normalize();
+        assert_eq!(hdr.cmdline(), [b'a'; 600]);
+    }
+
+    #[test]
+    fn semantic_eq() {
+        let mut a = header_v3(0, 0);
+        a.set_cmdline(b"console=ttyMSM0").unwrap();
+        let mut b = a.clone();
+        b.cmdline[100] = 0xff;
+        let (a, b) = (Header::V3(a), Header::V3(b));
+        assert_ne!(a, b);
+        assert!(a.semantic_eq(&b));
+
+        let mut c = a.clone();
+        c.set_cmdline(b"quiet").unwrap();
+        assert!(!a.semantic_eq(&c));
+    }
+
+    #[test]
+    fn hash_set_keying() {
+        use std::collections::HashSet;
+
+        // The boxed command line arrays must hash by content, not by pointer
+        let mut a = header_v0(HeaderV0Versioned::V0);
+        a.cmdline_part_1[..5].copy_from_slice(b"quiet");
+        let mut b = header_v0(HeaderV0Versioned::V0);
+        b.cmdline_part_1[..5].copy_from_slice(b"quiet");
+        assert!(!std::ptr::eq(&*a.cmdline_part_1, &*b.cmdline_part_1));
+        let set = HashSet::from([Header::V0(a.clone()), Header::V0(b.clone())]);
+        assert_eq!(set.len(), 1);
+
+        b.cmdline_part_2[0] = b'x';
+        let set = HashSet::from([Header::V0(a), Header::V0(b)]);
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn read_dtbs() {
+        let mut dtbs = dtb::tests::fdt(0x100);
+        dtbs.extend(dtb::tests::fdt(0x80));
+        let mut hdr = header_v0(HeaderV0Versioned::V2 {
+            recovery_dtbo_size: 0,
+            recovery_dtbo_addr: 0,
+            dtb_size: dtbs.len() as u32,
+            dtb_addr: 0,
+        });
+        hdr.kernel_size = 100;
+        let mut image = build_image(&Header::V0(hdr.clone()));
+        let position = hdr.dtb_position().unwrap();
+        image[position..][..dtbs.len()].copy_from_slice(&dtbs);
+
+        let fdts = hdr.read_dtbs(&mut std::io::Cursor::new(&image)).unwrap();
+        assert_eq!(fdts, [&dtbs[..0x100], &dtbs[0x100..]]);
+
+        let v0 = header_v0(HeaderV0Versioned::V0);
+        let image = build_image(&Header::V0(v0.clone()));
+        assert!(v0
+            .read_dtbs(&mut std::io::Cursor::new(&image))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn recovery_overlay_kind() {
+        let hdr = header_v0(HeaderV0Versioned::V1 {
+            recovery_dtbo_size: 100,
+            recovery_dtbo_addr: 0x11f00000,
+        });
+        let mut image = build_image(&Header::V0(hdr.clone()));
+        let position = hdr.recovery_dtbo_position().unwrap();
+        let kind = |image: &[u8]| {
+            hdr.recovery_overlay_kind(&mut std::io::Cursor::new(image))
+                .unwrap()
+        };
+        assert_eq!(kind(&image), OverlayKind::Unknown);
+
+        image[position..][..4].copy_from_slice(&DtboHeader::MAGIC);
+        assert_eq!(kind(&image), OverlayKind::Dtbo);
+        image[position..][..6].copy_from_slice(b"070701");
+        assert_eq!(kind(&image), OverlayKind::Acpio);
+
+        let v0 = header_v0(HeaderV0Versioned::V0);
+        assert_eq!(
+            v0.recovery_overlay_kind(&mut std::io::Cursor::new(&image))
+                .unwrap(),
+            OverlayKind::Unknown
+        );
+    }
+
+    #[test]
+    fn trailing_padding() {
+        let mut v0 = header_v0(HeaderV0Versioned::V0);
+        v0.kernel_size = 2048;
+        v0.ramdisk_size = 2049;
+        let hdr = Header::V0(v0);
+        assert_eq!(hdr.trailing_padding(SectionKind::Kernel), 0);
+        assert_eq!(hdr.trailing_padding(SectionKind::Ramdisk), 2047);
+        assert_eq!(hdr.trailing_padding(SectionKind::Second), 0);
+        assert_eq!(hdr.trailing_padding(SectionKind::Dtb), 0);
+    }
+
+    #[test]
+    fn validate_signature() {
+        let mut hdr = header_v3(5000, 100);
+        // Header, kernel (2 pages) and ramdisk (1 page)
+        assert_eq!(hdr.bootsig_position(), 4 * 4096);
+        let image_len = 5 * 4096;
+
+        hdr.v4_signature_size = Some(4096);
+        Header::V3(hdr.clone()).validate(image_len).unwrap();
+
+        hdr.v4_signature_size = Some(0);
+        assert!(matches!(
+            Header::V3(hdr.clone()).validate(image_len),
+            Err(Error::EmptySignature)
+        ));
+
+        hdr.v4_signature_size = Some(HeaderV3::MAX_SIGNATURE_SIZE + 1);
+        assert!(matches!(
+            Header::V3(hdr.clone()).validate(image_len),
+            Err(Error::SignatureTooLarge { .. })
+        ));
+
+        hdr.v4_signature_size = Some(8192);
+        assert!(matches!(
+            Header::V3(hdr).validate(image_len),
+            Err(Error::SignatureOutOfBounds {
+                end: 0x6000,
+                image_len: 0x5000
+            })
+        ));
+    }
+
+    #[test]
+    fn check_avb_footer() {
+        use crate::avb::tests::footer;
+
+        let mut hdr = header_v3(5000, 100);
+        hdr.v4_signature_size = Some(4096);
+        // The signature ends at 5 pages
+        let hdr = Header::V3(hdr);
+        let image_len = 6 * 4096;
+        let parse = |footer: Vec<u8>| {
+            AvbFooter::read_from_end(&mut std::io::Cursor::new(footer))
+                .unwrap()
+                .unwrap()
+        };
+        hdr.check_avb_footer(&parse(footer(5 * 4096, 5 * 4096, 1600)), image_len)
+            .unwrap();
+
+        let err = hdr
+            .check_avb_footer(&parse(footer(4 * 4096, 4 * 4096, 1600)), image_len)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::AvbFooterMismatch {
+                signature_end: 0x5000,
+                original_image_size: 0x4000,
+                vbmeta_offset: 0x4000
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "boot signature ends at 20480, but the AVB footer records an original image size of 16384 and vbmeta at 16384"
+        );
+
+        Header::V3(header_v3(5000, 100))
+            .check_avb_footer(&parse(footer(0, 0, 0)), image_len)
+            .unwrap();
+
+        // The vbmeta image runs into the footer
+        let err = hdr
+            .check_avb_footer(&parse(footer(5 * 4096, 5 * 4096, 4096)), image_len)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::VbmetaOutOfBounds {
+                end: 0x6000,
+                footer_position: 0x5fc0
+            }
+        ));
+        assert!(matches!(
+            hdr.check_avb_footer(&parse(footer(5 * 4096, u64::MAX, 1)), image_len),
+            Err(Error::VbmetaOutOfBounds { end: u64::MAX, .. })
+        ));
+    }
+}
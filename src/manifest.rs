@@ -0,0 +1,339 @@
+use crate::{
+    Error, Header, HeaderV0, HeaderV0Versioned, HeaderV3, OsVersionPatch, SectionKind, TrailingTag,
+};
+
+/// Record of a boot image's header fields and section layout, for reproducible
+/// repacking
+///
+/// With the `serde` feature, this can be (de)serialized, e.g. as the `manifest.json`
+/// written by `unpack_bootimg` and read by `mkbootimg`. Byte string fields are
+/// serialized as hex with trailing zeroes trimmed.
+///
+/// Fields absent in the header's version are zero or empty.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Manifest {
+    /// Header version
+    pub header_version: u32,
+    /// Page size in bytes
+    pub page_size: u32,
+    /// OS version and patch level
+    pub osversionpatch: u32,
+    /// Kernel physical load address (v0-v2)
+    pub kernel_addr: u32,
+    /// Ramdisk physical load address (v0-v2)
+    pub ramdisk_addr: u32,
+    /// Second bootloader physical load address (v0-v2)
+    pub second_bootloader_addr: u32,
+    /// Kernel tags physical load address (v0-v2)
+    pub tags_addr: u32,
+    /// Recovery DTBO/ACPIO physical load address (v1-v2)
+    pub recovery_dtbo_addr: u64,
+    /// DTB physical load address (v2)
+    pub dtb_addr: u64,
+    /// Board or product name (v0-v2)
+    #[cfg_attr(feature = "serde", serde(with = "hex"))]
+    pub board_name: Vec<u8>,
+    /// Kernel command line
+    #[cfg_attr(feature = "serde", serde(with = "hex"))]
+    pub cmdline: Vec<u8>,
+    /// Hash digest (v0-v2)
+    #[cfg_attr(feature = "serde", serde(with = "hex"))]
+    pub hash_digest: Vec<u8>,
+    /// Sections present in the header's version, in on-disk order
+    pub sections: Vec<ManifestSection>,
+    /// Vendor tag after the sections, to append when repacking
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub trailing_tag: Option<TrailingTag>,
+}
+
+/// Original layout of a section, as recorded in a [`Manifest`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ManifestSection {
+    /// Kind of the section
+    pub kind: SectionKind,
+    /// Offset of the section in the image
+    pub offset: u64,
+    /// Size of the section, excluding padding
+    pub size: u32,
+    /// Padding after the section, up to the page size
+    pub padding: u64,
+}
+
+impl Manifest {
+    /// Records a header's fields and section layout.
+    ///
+    /// The [`trailing_tag`](Self::trailing_tag) isn't part of the header, so it's
+    /// left `None`.
+    pub fn from_header(hdr: &Header) -> Self {
+        let page_size = hdr.page_size() as u64;
+        let sections = hdr
+            .sections()
+            .into_iter()
+            .map(|section| ManifestSection {
+                kind: section.kind,
+                offset: section.position as u64,
+                size: section.size,
+                padding: (section.size as u64).next_multiple_of(page_size) - section.size as u64,
+            })
+            .collect();
+        let mut manifest = Self {
+            header_version: hdr.header_version(),
+            page_size: page_size as u32,
+            osversionpatch: hdr.osversionpatch().0,
+            kernel_addr: 0,
+            ramdisk_addr: 0,
+            second_bootloader_addr: 0,
+            tags_addr: 0,
+            recovery_dtbo_addr: 0,
+            dtb_addr: 0,
+            board_name: Vec::new(),
+            cmdline: Vec::new(),
+            hash_digest: Vec::new(),
+            sections,
+            trailing_tag: None,
+        };
+        match hdr {
+            Header::V0(v0) => {
+                let (_, recovery_dtbo_addr, _, dtb_addr) = v0.versioned.fields();
+                manifest.kernel_addr = v0.kernel_addr;
+                manifest.ramdisk_addr = v0.ramdisk_addr;
+                manifest.second_bootloader_addr = v0.second_bootloader_addr;
+                manifest.tags_addr = v0.tags_addr;
+                manifest.recovery_dtbo_addr = recovery_dtbo_addr;
+                manifest.dtb_addr = dtb_addr;
+                manifest.board_name = trim_zeroes(&v0.board_name);
+                manifest.cmdline =
+                    trim_zeroes(&[&v0.cmdline_part_1[..], &v0.cmdline_part_2[..]].concat());
+                manifest.hash_digest = trim_zeroes(&v0.hash_digest);
+            }
+            Header::V3(v3) => manifest.cmdline = trim_zeroes(&*v3.cmdline),
+        }
+        manifest
+    }
+
+    /// Returns the recorded size of a section, or 0 if it isn't recorded.
+    #[must_use]
+    pub fn section_size(&self, kind: SectionKind) -> u32 {
+        self.sections
+            .iter()
+            .find(|section| section.kind == kind)
+            .map_or(0, |section| section.size)
+    }
+
+    /// Reconstructs the header.
+    ///
+    /// The section sizes are taken from [`sections`](Self::sections). Offsets and
+    /// padding are only recorded for auditing, since they follow from the sizes.
+    /// Non-empty sections that the header version doesn't have are rejected, as are
+    /// header versions other than 0-4.
+    pub fn to_header(&self) -> Result<Header, Error> {
+        if !crate::SUPPORTED_BOOT_VERSIONS.contains(&self.header_version) {
+            return Err(Error::UnsupportedHeaderVersion {
+                version: self.header_version,
+            });
+        }
+        if let Some(section) = self
+            .sections
+            .iter()
+            .find(|section| section.size != 0 && !section.kind.in_version(self.header_version))
+        {
+            return Err(Error::SectionNotInVersion {
+                kind: section.kind,
+                version: self.header_version,
+            });
+        }
+        let size = |kind| self.section_size(kind);
+        match self.header_version {
+            0..=2 => {
+                let mut hdr = HeaderV0 {
+                    kernel_size: size(SectionKind::Kernel),
+                    kernel_addr: self.kernel_addr,
+                    ramdisk_size: size(SectionKind::Ramdisk),
+                    ramdisk_addr: self.ramdisk_addr,
+                    second_bootloader_size: size(SectionKind::Second),
+                    second_bootloader_addr: self.second_bootloader_addr,
+                    tags_addr: self.tags_addr,
+                    page_size: self.page_size,
+                    osversionpatch: OsVersionPatch(self.osversionpatch),
+                    board_name: pad_zeroes(&self.board_name)?,
+                    cmdline_part_1: Box::new([0; 512]),
+                    hash_digest: pad_zeroes(&self.hash_digest)?,
+                    cmdline_part_2: Box::new([0; 1024]),
+                    versioned: HeaderV0Versioned::V0,
+                    trailing: Vec::new(),
+                };
+                let cmdline: [u8; 1536] = pad_zeroes(&self.cmdline)?;
+                hdr.cmdline_part_1.copy_from_slice(&cmdline[..512]);
+                hdr.cmdline_part_2.copy_from_slice(&cmdline[512..]);
+                hdr.versioned = match self.header_version {
+                    0 => HeaderV0Versioned::V0,
+                    1 => HeaderV0Versioned::V1 {
+                        recovery_dtbo_size: size(SectionKind::RecoveryDtbo),
+                        recovery_dtbo_addr: self.recovery_dtbo_addr,
+                    },
+                    _ => HeaderV0Versioned::V2 {
+                        recovery_dtbo_size: size(SectionKind::RecoveryDtbo),
+                        recovery_dtbo_addr: self.recovery_dtbo_addr,
+                        dtb_size: size(SectionKind::Dtb),
+                        dtb_addr: self.dtb_addr,
+                    },
+                };
+                Ok(Header::V0(hdr))
+            }
+            _ => {
+                if self.page_size != HeaderV3::PAGE_SIZE as u32 {
+                    return Err(Error::InvalidPageSize {
+                        page_size: self.page_size,
+                    });
+                }
+                Ok(Header::V3(HeaderV3 {
+                    kernel_size: size(SectionKind::Kernel),
+                    ramdisk_size: size(SectionKind::Ramdisk),
+                    osversionpatch: OsVersionPatch(self.osversionpatch),
+                    cmdline: Box::new(pad_zeroes(&self.cmdline)?),
+                    v4_signature_size: (self.header_version == 4)
+                        .then(|| size(SectionKind::BootSignature)),
+                    trailing: Vec::new(),
+                }))
+            }
+        }
+    }
+}
+
+fn trim_zeroes(bytes: &[u8]) -> Vec<u8> {
+    let len = bytes.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+    bytes[..len].to_vec()
+}
+
+fn pad_zeroes<const N: usize>(bytes: &[u8]) -> Result<[u8; N], Error> {
+    let mut buf = [0; N];
+    buf.get_mut(..bytes.len())
+        .ok_or(Error::FieldTooLong {
+            len: bytes.len(),
+            max: N,
+        })?
+        .copy_from_slice(bytes);
+    Ok(buf)
+}
+
+#[cfg(feature = "serde")]
+mod hex {
+    use serde::{de::Error as _, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&bytes.iter().map(|b| format!("{b:02x}")).collect::<String>())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        if hex.len() % 2 != 0 {
+            return Err(D::Error::custom("odd number of hex digits"));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| {
+                hex.get(i..i + 2)
+                    .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                    .ok_or_else(|| D::Error::custom("invalid hex digit"))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{header_v0, header_v3};
+
+    fn headers() -> [Header; 2] {
+        let mut v2 = header_v0(HeaderV0Versioned::V2 {
+            recovery_dtbo_size: 100,
+            recovery_dtbo_addr: 0x1000,
+            dtb_size: 5000,
+            dtb_addr: 0x11f00000,
+        });
+        v2.kernel_size = 3000;
+        v2.board_name[..3].copy_from_slice(b"foo");
+        v2.hash_digest = [0xaa; 32];
+        v2.set_cmdline(&[b'a'; 600]).unwrap();
+        let mut v4 = header_v3(10000, 20);
+        v4.v4_signature_size = Some(4096);
+        v4.set_cmdline(b"console=ttyS0").unwrap();
+        [Header::V0(v2), Header::V3(v4)]
+    }
+
+    #[test]
+    fn round_trip() {
+        for hdr in headers() {
+            let manifest = Manifest::from_header(&hdr);
+            assert_eq!(manifest.to_header().unwrap(), hdr);
+        }
+
+        let manifest = Manifest::from_header(&headers()[0]);
+        assert_eq!(manifest.board_name, b"foo");
+        assert_eq!(
+            manifest.sections[4],
+            ManifestSection {
+                kind: SectionKind::Dtb,
+                offset: 4 * 2048,
+                size: 5000,
+                padding: 3 * 2048 - 5000,
+            }
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let mut manifest = Manifest::from_header(&headers()[0]);
+        manifest.board_name = vec![b'x'; 17];
+        assert!(matches!(
+            manifest.to_header(),
+            Err(Error::FieldTooLong { len: 17, max: 16 })
+        ));
+
+        let mut manifest = Manifest::from_header(&headers()[1]);
+        manifest.page_size = 2048;
+        assert!(manifest.to_header().is_err());
+
+        let mut manifest = Manifest::from_header(&headers()[1]);
+        manifest.sections.push(ManifestSection {
+            kind: SectionKind::Second,
+            offset: 0,
+            size: 100,
+            padding: 0,
+        });
+        assert!(matches!(
+            manifest.to_header(),
+            Err(Error::SectionNotInVersion {
+                kind: SectionKind::Second,
+                version: 4
+            })
+        ));
+
+        let mut manifest = Manifest::from_header(&headers()[1]);
+        manifest.header_version = 5;
+        assert!(matches!(
+            manifest.to_header(),
+            Err(Error::UnsupportedHeaderVersion { version: 5 })
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip() {
+        for hdr in headers() {
+            let json = serde_json::to_string(&Manifest::from_header(&hdr)).unwrap();
+            let manifest: Manifest = serde_json::from_str(&json).unwrap();
+            assert_eq!(manifest.to_header().unwrap(), hdr);
+        }
+
+        let json = serde_json::to_value(Manifest::from_header(&headers()[0])).unwrap();
+        assert_eq!(json["board_name"], "666f6f");
+        assert_eq!(json["sections"][0]["kind"], "kernel");
+    }
+}
@@ -0,0 +1,309 @@
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use crate::{take_until_null, Header, HeaderV0Versioned};
+
+/// Paths of extracted sections, used as `mkbootimg` arguments
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SectionPaths {
+    /// Kernel path
+    pub kernel: PathBuf,
+    /// Ramdisk path
+    pub ramdisk: PathBuf,
+    /// Second stage bootloader path
+    pub second: PathBuf,
+    /// Recovery DTBO/ACPIO path
+    pub recovery_dtbo: PathBuf,
+    /// DTB path
+    pub dtb: PathBuf,
+}
+impl SectionPaths {
+    /// Returns the paths of sections extracted to `dir` by `unpack_bootimg`.
+    pub fn in_dir(dir: &Path) -> Self {
+        Self {
+            kernel: dir.join("kernel"),
+            ramdisk: dir.join("ramdisk"),
+            second: dir.join("second"),
+            recovery_dtbo: dir.join("recovery_dtbo"),
+            dtb: dir.join("dtb"),
+        }
+    }
+}
+
+impl Header {
+    /// Returns the `mkbootimg` arguments that reconstruct this boot image from
+    /// sections extracted to `paths`.
+    ///
+    /// Empty optional sections and an empty kernel are left out. The v4 boot signature
+    /// is left out too, since `mkbootimg` generates it when signing.
+    ///
+    /// Like AOSP's `unpack_bootimg`, the load addresses of versions 0-2 are given as
+    /// absolute offsets with `--base 0x00000000`. See
+    /// [`to_mkbootimg_args_with_base`](Self::to_mkbootimg_args_with_base) to factor out
+    /// a base instead.
+    #[must_use]
+    pub fn to_mkbootimg_args(&self, paths: &SectionPaths) -> Vec<OsString> {
+        self.to_mkbootimg_args_with_base(paths, 0)
+    }
+    /// Returns the `mkbootimg` arguments that reconstruct this boot image, like
+    /// [`to_mkbootimg_args`](Self::to_mkbootimg_args), but with the load addresses of
+    /// versions 0-2 given as offsets from `base`.
+    ///
+    /// Offsets wrap around if `base` is above an address, which `mkbootimg` rejects;
+    /// [`mkbootimg_base`](Self::mkbootimg_base) returns a base that avoids this.
+    #[must_use]
+    pub fn to_mkbootimg_args_with_base(&self, paths: &SectionPaths, base: u32) -> Vec<OsString> {
+        let mut args: Vec<OsString> = vec![
+            "--header_version".into(),
+            self.header_version().to_string().into(),
+        ];
+        let osversionpatch = self.osversionpatch();
+        if !osversionpatch.is_unset() {
+            args.extend([
+                "--os_version".into(),
+                osversionpatch.version().to_string().into(),
+            ]);
+            if osversionpatch.patch().month() != 0 {
+                args.extend([
+                    "--os_patch_level".into(),
+                    osversionpatch.patch().to_string().into(),
+                ]);
+            }
+        }
+        if self.kernel_size() != 0 {
+            args.extend(["--kernel".into(), paths.kernel.clone().into()]);
+        }
+        args.extend(["--ramdisk".into(), paths.ramdisk.clone().into()]);
+
+        if let Self::V0(v0) = self {
+            if v0.second_bootloader_size != 0 {
+                args.extend(["--second".into(), paths.second.clone().into()]);
+            }
+            if let HeaderV0Versioned::V1 {
+                recovery_dtbo_size, ..
+            }
+            | HeaderV0Versioned::V2 {
+                recovery_dtbo_size, ..
+            } = v0.versioned
+            {
+                if recovery_dtbo_size != 0 {
+                    args.extend(["--recovery_dtbo".into(), paths.recovery_dtbo.clone().into()]);
+                }
+            }
+            if let HeaderV0Versioned::V2 { dtb_size, .. } = v0.versioned {
+                if dtb_size != 0 {
+                    args.extend(["--dtb".into(), paths.dtb.clone().into()]);
+                }
+            }
+
+            args.extend([
+                "--pagesize".into(),
+                format!("0x{:08x}", v0.page_size).into(),
+                "--base".into(),
+                format!("0x{base:08x}").into(),
+                "--kernel_offset".into(),
+                format!("0x{:08x}", v0.kernel_addr.wrapping_sub(base)).into(),
+                "--ramdisk_offset".into(),
+                format!("0x{:08x}", v0.ramdisk_addr.wrapping_sub(base)).into(),
+                "--second_offset".into(),
+                format!("0x{:08x}", v0.second_bootloader_addr.wrapping_sub(base)).into(),
+                "--tags_offset".into(),
+                format!("0x{:08x}", v0.tags_addr.wrapping_sub(base)).into(),
+            ]);
+            if let HeaderV0Versioned::V2 { dtb_addr, .. } = v0.versioned {
+                let dtb_offset = dtb_addr.wrapping_sub(base.into());
+                args.extend(["--dtb_offset".into(), format!("0x{dtb_offset:016x}").into()]);
+            }
+            args.extend(["--board".into(), os_string(take_until_null(&v0.board_name))]);
+        } else {
+            // mkbootimg fixes the page size of v3/v4 images at 4096 bytes, but accepts
+            // the argument
+            args.extend(["--pagesize".into(), format!("0x{:08x}", 4096).into()]);
+        }
+        args.extend(["--cmdline".into(), os_string(&self.cmdline())]);
+        args
+    }
+    /// Returns a `--base` for [`to_mkbootimg_args_with_base`] that puts the kernel at
+    /// `mkbootimg`'s default kernel offset of `0x00008000`.
+    ///
+    /// Returns 0, i.e. absolute offsets, for versions 3 and 4, which have no load
+    /// addresses, or if the base would be above any load address.
+    ///
+    /// [`to_mkbootimg_args_with_base`]: Self::to_mkbootimg_args_with_base
+    #[must_use]
+    pub fn mkbootimg_base(&self) -> u32 {
+        const KERNEL_OFFSET: u32 = 0x00008000;
+
+        let Self::V0(v0) = self else {
+            return 0;
+        };
+        let Some(base) = v0.kernel_addr.checked_sub(KERNEL_OFFSET) else {
+            return 0;
+        };
+        let dtb_addr = match v0.versioned {
+            HeaderV0Versioned::V2 { dtb_addr, .. } => dtb_addr,
+            _ => u64::MAX,
+        };
+        let addrs = [v0.ramdisk_addr, v0.second_bootloader_addr, v0.tags_addr];
+        if addrs.iter().all(|&addr| addr >= base) && dtb_addr >= base.into() {
+            base
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(unix)]
+fn os_string(bytes: &[u8]) -> OsString {
+    std::os::unix::ffi::OsStringExt::from_vec(bytes.to_vec())
+}
+#[cfg(not(unix))]
+fn os_string(bytes: &[u8]) -> OsString {
+    String::from_utf8_lossy(bytes).into_owned().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::{header_v0, header_v3},
+        OsPatch, OsVersion, OsVersionPatch,
+    };
+
+    #[test]
+    fn v2_args() {
+        let mut hdr = header_v0(HeaderV0Versioned::V2 {
+            recovery_dtbo_size: 0,
+            recovery_dtbo_addr: 0,
+            dtb_size: 100,
+            dtb_addr: 0x11f00000,
+        });
+        hdr.osversionpatch = OsVersionPatch::new(OsVersion::new(12, 0, 0), OsPatch::new(2024, 6));
+        hdr.kernel_size = 100;
+        hdr.board_name[..3].copy_from_slice(b"foo");
+        hdr.cmdline_part_1[..13].copy_from_slice(b"console=ttyS0");
+
+        let args = Header::V0(hdr).to_mkbootimg_args(&SectionPaths::in_dir(Path::new("out")));
+        assert_eq!(
+            args,
+            [
+                "--header_version",
+                "2",
+                "--os_version",
+                "12.0.0",
+                "--os_patch_level",
+                "2024-06",
+                "--kernel",
+                "out/kernel",
+                "--ramdisk",
+                "out/ramdisk",
+                "--dtb",
+                "out/dtb",
+                "--pagesize",
+                "0x00000800",
+                "--base",
+                "0x00000000",
+                "--kernel_offset",
+                "0x10008000",
+                "--ramdisk_offset",
+                "0x11000000",
+                "--second_offset",
+                "0x10f00000",
+                "--tags_offset",
+                "0x10000100",
+                "--dtb_offset",
+                "0x0000000011f00000",
+                "--board",
+                "foo",
+                "--cmdline",
+                "console=ttyS0",
+            ]
+        );
+    }
+
+    #[test]
+    fn v2_args_with_base() {
+        let mut hdr = header_v0(HeaderV0Versioned::V2 {
+            recovery_dtbo_size: 0,
+            recovery_dtbo_addr: 0,
+            dtb_size: 0,
+            dtb_addr: 0x11f00000,
+        });
+        hdr.kernel_size = 100;
+        let hdr = Header::V0(hdr);
+        assert_eq!(hdr.mkbootimg_base(), 0x10000000);
+
+        let args = hdr.to_mkbootimg_args_with_base(
+            &SectionPaths::in_dir(Path::new("out")),
+            hdr.mkbootimg_base(),
+        );
+        let offsets = args.iter().position(|arg| arg == "--base").unwrap();
+        assert_eq!(
+            args[offsets..][..12],
+            [
+                "--base",
+                "0x10000000",
+                "--kernel_offset",
+                "0x00008000",
+                "--ramdisk_offset",
+                "0x01000000",
+                "--second_offset",
+                "0x00f00000",
+                "--tags_offset",
+                "0x00000100",
+                "--dtb_offset",
+                "0x0000000001f00000",
+            ]
+        );
+
+        let Header::V0(mut v0) = hdr else {
+            unreachable!()
+        };
+        v0.second_bootloader_addr = 0;
+        assert_eq!(Header::V0(v0).mkbootimg_base(), 0);
+        assert_eq!(Header::V3(header_v3(0, 0)).mkbootimg_base(), 0);
+    }
+
+    #[test]
+    fn v3_v4_args() {
+        let mut hdr = header_v3(100, 10);
+        hdr.osversionpatch = OsVersionPatch::new(OsVersion::new(13, 0, 0), OsPatch::new(2024, 12));
+        hdr.set_cmdline(b"console=ttyS0").unwrap();
+        let paths = SectionPaths::in_dir(Path::new("out"));
+        let expected = |version| {
+            [
+                "--header_version",
+                version,
+                "--os_version",
+                "13.0.0",
+                "--os_patch_level",
+                "2024-12",
+                "--kernel",
+                "out/kernel",
+                "--ramdisk",
+                "out/ramdisk",
+                "--pagesize",
+                "0x00001000",
+                "--cmdline",
+                "console=ttyS0",
+            ]
+        };
+
+        assert_eq!(
+            Header::V3(hdr.clone()).to_mkbootimg_args(&paths),
+            expected("3")
+        );
+        hdr.v4_signature_size = Some(4096);
+        assert_eq!(
+            Header::V3(hdr.clone()).to_mkbootimg_args(&paths),
+            expected("4")
+        );
+
+        hdr.kernel_size = 0;
+        let args = Header::V3(hdr).to_mkbootimg_args(&paths);
+        assert!(!args.iter().any(|arg| arg == "--kernel"));
+        assert!(args.iter().any(|arg| arg == "--ramdisk"));
+    }
+}
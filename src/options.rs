@@ -0,0 +1,52 @@
+/// Options for [`Header::parse_with_options`](crate::Header::parse_with_options)
+///
+/// Start from [`ParseOptions::default`] and set the fields you need:
+///
+/// ```
+/// let mut options = abootimg_oxide::ParseOptions::default();
+/// options.allow_newer = true;
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct ParseOptions {
+    /// Unit of the section sizes stored in the header
+    pub size_unit: SizeUnit,
+    /// Accept v1-v4 headers declaring a larger size than known, as a newer header
+    /// with extra fields would, instead of failing.
+    ///
+    /// The extra bytes are kept in the header's `trailing` field. A v3/v4 header
+    /// larger than its 4096-byte page still fails with
+    /// [`Error::HeaderSizeMismatch`](crate::Error::HeaderSizeMismatch).
+    pub allow_newer: bool,
+    /// Largest section size in bytes to accept, or `None` for no limit.
+    ///
+    /// This guards tools handling untrusted images against headers declaring huge
+    /// sections to exhaust memory or disk space when extracting. Defaults to
+    /// [`DEFAULT_MAX_SECTION_SIZE`](Self::DEFAULT_MAX_SECTION_SIZE).
+    pub max_section_size: Option<u64>,
+}
+impl ParseOptions {
+    /// Default [`max_section_size`](Self::max_section_size), 512 MiB
+    pub const DEFAULT_MAX_SECTION_SIZE: u64 = 512 * 1024 * 1024;
+}
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            size_unit: SizeUnit::default(),
+            allow_newer: false,
+            max_section_size: Some(Self::DEFAULT_MAX_SECTION_SIZE),
+        }
+    }
+}
+
+/// Unit of the section sizes stored in a header
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum SizeUnit {
+    /// Bytes, as used by `mkbootimg`
+    #[default]
+    Bytes,
+    /// 512-byte sectors
+    ///
+    /// This is only for compatibility with images made by some very old tools.
+    Sectors,
+}
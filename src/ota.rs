@@ -0,0 +1,149 @@
+//! Minimal reader for OTA `payload.bin` metadata
+//!
+//! Only enough of the payload manifest, a `DeltaArchiveManifest` protobuf message, is
+//! decoded to list the partitions.
+
+use std::io::{Read, Seek};
+
+use crate::Error;
+
+/// OTA payload magic
+const MAGIC: &[u8; 4] = b"CrAU";
+/// Field number of `DeltaArchiveManifest.partitions`
+const PARTITIONS_FIELD: u64 = 13;
+/// Field number of `PartitionUpdate.partition_name`
+const PARTITION_NAME_FIELD: u64 = 1;
+
+/// Reads the names of the partitions updated by an OTA `payload.bin`, in manifest
+/// order.
+///
+/// This only reads the payload's header and manifest, e.g. to check that it contains
+/// a `boot` partition. Extracting partitions isn't supported.
+pub fn read_ota_partition_names<R: Read + Seek>(reader: &mut R) -> Result<Vec<String>, Error> {
+    let mut header = [0; 20];
+    reader.read_exact(&mut header)?;
+    if &header[..4] != MAGIC {
+        return Err(Error::NotOtaPayload);
+    }
+    let version = u64::from_be_bytes(header[4..12].try_into().unwrap());
+    let manifest_size = u64::from_be_bytes(header[12..20].try_into().unwrap());
+    if version >= 2 {
+        // Skip the metadata signature size
+        reader.read_exact(&mut [0; 4])?;
+    }
+
+    let mut manifest = Vec::new();
+    reader.take(manifest_size).read_to_end(&mut manifest)?;
+    if manifest.len() as u64 != manifest_size {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+
+    let mut names = Vec::new();
+    let mut message = &manifest[..];
+    while !message.is_empty() {
+        let (PARTITIONS_FIELD, Some(mut partition)) = field(&mut message)? else {
+            continue;
+        };
+        while !partition.is_empty() {
+            if let (PARTITION_NAME_FIELD, Some(name)) = field(&mut partition)? {
+                let name = std::str::from_utf8(name).map_err(|_| Error::InvalidOtaManifest)?;
+                names.push(name.to_owned());
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// Reads one field from the start of a protobuf message, returning its number and,
+/// if it's length-delimited, its payload.
+fn field<'a>(message: &mut &'a [u8]) -> Result<(u64, Option<&'a [u8]>), Error> {
+    let key = varint(message)?;
+    let skip = match key & 7 {
+        0 => {
+            varint(message)?;
+            0
+        }
+        1 => 8,
+        2 => {
+            let len = usize::try_from(varint(message)?).map_err(|_| Error::InvalidOtaManifest)?;
+            let bytes = message.get(..len).ok_or(Error::InvalidOtaManifest)?;
+            *message = &message[len..];
+            return Ok((key >> 3, Some(bytes)));
+        }
+        5 => 4,
+        _ => return Err(Error::InvalidOtaManifest),
+    };
+    *message = message.get(skip..).ok_or(Error::InvalidOtaManifest)?;
+    Ok((key >> 3, None))
+}
+
+/// Reads a base 128 varint from the start of `bytes`.
+fn varint(bytes: &mut &[u8]) -> Result<u64, Error> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first().ok_or(Error::InvalidOtaManifest)?;
+        *bytes = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(Error::InvalidOtaManifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// Encodes a length-delimited protobuf field.
+    fn bytes_field(field: u8, bytes: &[u8]) -> Vec<u8> {
+        let mut encoded = vec![field << 3 | 2, bytes.len() as u8];
+        encoded.extend(bytes);
+        encoded
+    }
+
+    #[test]
+    fn partition_names() {
+        let mut manifest = vec![1 << 3, 0x80, 0x20]; // block_size = 4096
+        for name in ["boot", "system"] {
+            let mut partition = bytes_field(1, name.as_bytes());
+            partition.extend([2 << 3 | 5, 1, 2, 3, 4]); // unknown fixed32 field
+            manifest.extend(bytes_field(13, &partition));
+        }
+        let mut payload = MAGIC.to_vec();
+        payload.extend(2u64.to_be_bytes());
+        payload.extend((manifest.len() as u64).to_be_bytes());
+        payload.extend(0u32.to_be_bytes());
+        payload.extend(&manifest);
+
+        assert_eq!(
+            read_ota_partition_names(&mut Cursor::new(&payload)).unwrap(),
+            ["boot", "system"]
+        );
+
+        assert!(matches!(
+            read_ota_partition_names(&mut Cursor::new(&payload[..payload.len() - 1])),
+            Err(Error::Io(_))
+        ));
+        payload[0] = b'X';
+        assert!(matches!(
+            read_ota_partition_names(&mut Cursor::new(&payload)),
+            Err(Error::NotOtaPayload)
+        ));
+    }
+
+    #[test]
+    fn malformed_manifest() {
+        let manifest = [13 << 3 | 2, 10, 1];
+        let mut payload = MAGIC.to_vec();
+        payload.extend(1u64.to_be_bytes());
+        payload.extend((manifest.len() as u64).to_be_bytes());
+        payload.extend(manifest);
+        assert!(matches!(
+            read_ota_partition_names(&mut Cursor::new(&payload)),
+            Err(Error::InvalidOtaManifest)
+        ));
+    }
+}
@@ -0,0 +1,81 @@
+use std::fmt;
+
+/// Kind of a section following the boot image header
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum SectionKind {
+    /// Kernel
+    Kernel,
+    /// Ramdisk
+    Ramdisk,
+    /// Second stage bootloader (v0-v2)
+    Second,
+    /// Recovery DTBO/ACPIO (v1-v2)
+    RecoveryDtbo,
+    /// DTB (v2)
+    Dtb,
+    /// Boot signature (v4)
+    BootSignature,
+}
+impl SectionKind {
+    /// Returns the section's name, as used for file names by `unpack_bootimg`.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Kernel => "kernel",
+            Self::Ramdisk => "ramdisk",
+            Self::Second => "second",
+            Self::RecoveryDtbo => "recovery_dtbo",
+            Self::Dtb => "dtb",
+            Self::BootSignature => "boot_signature",
+        }
+    }
+    /// Returns `true` if headers of the given version have this section.
+    #[must_use]
+    pub fn in_version(self, version: u32) -> bool {
+        match self {
+            Self::Kernel | Self::Ramdisk => version <= 4,
+            Self::Second => version <= 2,
+            Self::RecoveryDtbo => (1..=2).contains(&version),
+            Self::Dtb => version == 2,
+            Self::BootSignature => version == 4,
+        }
+    }
+}
+
+impl fmt::Display for SectionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Location of a section in the boot image
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Section {
+    /// Kind of the section
+    pub kind: SectionKind,
+    /// Position of the section in the boot image
+    pub position: usize,
+    /// Size of the section, excluding padding
+    pub size: u32,
+}
+
+/// Region of an image, including the header
+///
+/// With the `serde` feature, this can be serialized.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Region {
+    /// Name of the region
+    pub name: &'static str,
+    /// Offset of the region in the image
+    pub offset: u64,
+    /// Size of the region, excluding padding
+    pub size: u64,
+    /// Padding after the region, up to the page size
+    pub padding: u64,
+}
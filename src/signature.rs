@@ -0,0 +1,138 @@
+use std::io::{Read, Seek, Write};
+
+use crate::{Error, Header, HeaderV3, ImageWriter};
+
+impl HeaderV3 {
+    /// Removes the boot signature, converting a v4 header to v3.
+    ///
+    /// See [`strip_image_signature`] to rewrite a whole image.
+    pub fn strip_signature(&mut self) {
+        self.v4_signature_size = None;
+    }
+    /// Declares a boot signature of `size` bytes, converting a v3 header to v4.
+    ///
+    /// See [`attach_image_signature`] to rewrite a whole image.
+    pub fn attach_signature(&mut self, size: u32) {
+        self.v4_signature_size = Some(size);
+    }
+}
+
+/// Rewrites a v3 or v4 boot image without its boot signature, returning the writer.
+///
+/// The header is rewritten as v3 and the image ends after the ramdisk.
+pub fn strip_image_signature<R: Read + Seek, W: Write>(
+    reader: &mut R,
+    writer: W,
+) -> Result<W, Error> {
+    let mut hdr = match Header::parse(reader)? {
+        Header::V3(hdr) => hdr,
+        hdr => {
+            return Err(Error::UnsupportedHeaderVersion {
+                version: hdr.header_version(),
+            })
+        }
+    };
+    let image = Header::V3(hdr.clone()).extract_all(reader)?;
+    hdr.strip_signature();
+
+    let mut w = ImageWriter::new(writer);
+    w.write_header(&Header::V3(hdr))?;
+    w.write_kernel(&image.kernel)?;
+    w.write_ramdisk(&image.ramdisk)?;
+    w.finish()
+}
+
+/// Rewrites a v3 or v4 boot image with `signature` as its boot signature, returning
+/// the writer.
+///
+/// The header is rewritten as v4 and the signature starts on the page boundary after
+/// the ramdisk, replacing any existing signature.
+pub fn attach_image_signature<R: Read + Seek, W: Write>(
+    reader: &mut R,
+    writer: W,
+    signature: &[u8],
+) -> Result<W, Error> {
+    if signature.is_empty() {
+        return Err(Error::EmptySignature);
+    }
+    let size = match u32::try_from(signature.len()) {
+        Ok(size) if size <= HeaderV3::MAX_SIGNATURE_SIZE => size,
+        _ => {
+            return Err(Error::SignatureTooLarge {
+                size: signature.len() as u64,
+            })
+        }
+    };
+    let mut hdr = match Header::parse(reader)? {
+        Header::V3(hdr) => hdr,
+        hdr => {
+            return Err(Error::UnsupportedHeaderVersion {
+                version: hdr.header_version(),
+            })
+        }
+    };
+    let image = Header::V3(hdr.clone()).extract_all(reader)?;
+    hdr.attach_signature(size);
+
+    let mut w = ImageWriter::new(writer);
+    w.write_header(&Header::V3(hdr))?;
+    w.write_kernel(&image.kernel)?;
+    w.write_ramdisk(&image.ramdisk)?;
+    w.write_boot_signature(signature)?;
+    w.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::tests::{build_image, header_v3};
+
+    #[test]
+    fn strip() {
+        let mut hdr = header_v3(5000, 100);
+        hdr.v4_signature_size = Some(4096);
+        let signed = build_image(&Header::V3(hdr.clone()));
+        assert_eq!(signed.len(), 5 * 4096);
+
+        let stripped = strip_image_signature(&mut Cursor::new(&signed), Vec::new()).unwrap();
+        assert_eq!(stripped.len(), 4 * 4096);
+        let parsed = Header::parse(&mut Cursor::new(&stripped)).unwrap();
+        assert_eq!(parsed.header_version(), 3);
+        assert_eq!(
+            parsed.section_range(crate::SectionKind::BootSignature),
+            None
+        );
+
+        hdr.strip_signature();
+        assert_eq!(parsed, Header::V3(hdr.clone()));
+        assert_eq!(stripped, build_image(&Header::V3(hdr)));
+    }
+
+    #[test]
+    fn attach() {
+        let hdr = header_v3(5000, 100);
+        let unsigned = build_image(&Header::V3(hdr.clone()));
+        let signature = [0xaa; 1000];
+
+        let signed =
+            attach_image_signature(&mut Cursor::new(&unsigned), Vec::new(), &signature).unwrap();
+        assert_eq!(signed.len(), 5 * 4096);
+        assert_eq!(signed[4096..4 * 4096], unsigned[4096..]);
+        let parsed = Header::parse(&mut Cursor::new(&signed)).unwrap();
+        assert_eq!(parsed.header_version(), 4);
+        parsed.validate(signed.len() as u64).unwrap();
+        let extracted = parsed.extract_all(&mut Cursor::new(&signed)).unwrap();
+        assert_eq!(extracted.boot_signature.as_deref(), Some(&signature[..]));
+
+        assert!(matches!(
+            attach_image_signature(&mut Cursor::new(&unsigned), Vec::new(), &[]),
+            Err(Error::EmptySignature)
+        ));
+        assert!(matches!(
+            attach_image_signature(&mut Cursor::new(&unsigned), Vec::new(), &[0; 0x10001]),
+            Err(Error::SignatureTooLarge { size: 0x10001 })
+        ));
+    }
+}
@@ -0,0 +1,125 @@
+use crate::Header;
+
+/// Owned summary of a boot image header's most commonly used fields
+///
+/// See [`Header::summarize`]. With the `serde` feature, this can be (de)serialized.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HeaderSummary {
+    /// Header version
+    pub version: u32,
+    /// Kernel size in bytes
+    pub kernel_size: u32,
+    /// Ramdisk size in bytes
+    pub ramdisk_size: u32,
+    /// Page size in bytes
+    pub page_size: usize,
+    /// OS version, e.g. `12.0.0`
+    pub os_version: String,
+    /// OS patch level, e.g. `2024-06`, or `(none)` if unset
+    pub os_patch: String,
+    /// Kernel command line, with invalid UTF-8 replaced
+    pub cmdline: String,
+}
+
+impl Header {
+    /// Returns an owned summary of the header's most commonly used fields.
+    #[must_use]
+    pub fn summarize(&self) -> HeaderSummary {
+        let osversionpatch = self.osversionpatch();
+        HeaderSummary {
+            version: self.header_version(),
+            kernel_size: self.kernel_size(),
+            ramdisk_size: self.ramdisk_size(),
+            page_size: self.page_size(),
+            os_version: osversionpatch.version().to_string(),
+            os_patch: osversionpatch.patch().to_string(),
+            cmdline: String::from_utf8_lossy(&self.cmdline()).into_owned(),
+        }
+    }
+    /// Returns a deterministic file name for the image based on its metadata, e.g.
+    /// `boot-v2-12.0.0-2024-06.img`.
+    ///
+    /// The OS version and patch level are left out if unset. Characters other than
+    /// ASCII alphanumerics, `.`, `-` and `_` are replaced with `_`.
+    #[must_use]
+    pub fn suggested_filename(&self) -> String {
+        let osversionpatch = self.osversionpatch();
+        let mut name = format!("boot-v{}", self.header_version());
+        if !osversionpatch.is_unset() {
+            name += &format!("-{}", osversionpatch.version());
+            if osversionpatch.patch().month() != 0 {
+                name += &format!("-{}", osversionpatch.patch());
+            }
+        }
+        let name: String = name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        name + ".img"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        tests::{build_image, header_v0},
+        HeaderV0Versioned, OsPatch, OsVersion, OsVersionPatch,
+    };
+
+    #[test]
+    fn summarize() {
+        let mut hdr = header_v0(HeaderV0Versioned::V0);
+        hdr.kernel_size = 5000;
+        hdr.ramdisk_size = 100;
+        hdr.osversionpatch = OsVersionPatch::new(OsVersion::new(12, 0, 0), OsPatch::new(2024, 6));
+        hdr.set_cmdline(b"console=ttyMSM0").unwrap();
+        let image = build_image(&Header::V0(hdr));
+        let hdr = Header::parse(&mut Cursor::new(&image)).unwrap();
+
+        assert_eq!(
+            hdr.summarize(),
+            HeaderSummary {
+                version: 0,
+                kernel_size: 5000,
+                ramdisk_size: 100,
+                page_size: 2048,
+                os_version: "12.0.0".to_owned(),
+                os_patch: "2024-06".to_owned(),
+                cmdline: "console=ttyMSM0".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn suggested_filename() {
+        let mut hdr = header_v0(HeaderV0Versioned::V2 {
+            recovery_dtbo_size: 0,
+            recovery_dtbo_addr: 0,
+            dtb_size: 0,
+            dtb_addr: 0,
+        });
+        hdr.osversionpatch = OsVersionPatch::new(OsVersion::new(12, 0, 0), OsPatch::new(2024, 6));
+        assert_eq!(
+            Header::V0(hdr.clone()).suggested_filename(),
+            "boot-v2-12.0.0-2024-06.img"
+        );
+
+        hdr.osversionpatch = OsVersionPatch::new(OsVersion::new(12, 0, 0), OsPatch(0));
+        assert_eq!(
+            Header::V0(hdr.clone()).suggested_filename(),
+            "boot-v2-12.0.0.img"
+        );
+        hdr.osversionpatch = OsVersionPatch(0);
+        assert_eq!(Header::V0(hdr).suggested_filename(), "boot-v2.img");
+    }
+}
@@ -0,0 +1,95 @@
+use std::{
+    fmt,
+    io::{self, Read, Seek, SeekFrom},
+};
+
+/// Vendor tag appended after the sections of a boot image
+///
+/// See [`read_trailing_tag`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[non_exhaustive]
+pub enum TrailingTag {
+    /// `SEANDROIDENFORCE`, appended by Samsung to mark SEAndroid enforcing images
+    SeAndroidEnforce,
+    /// LG's "bump" magic, which some LG bootloaders check for
+    LgBump,
+}
+impl TrailingTag {
+    /// Returns the tag's bytes, as appended to the image.
+    #[must_use]
+    pub fn bytes(self) -> &'static [u8] {
+        match self {
+            Self::SeAndroidEnforce => b"SEANDROIDENFORCE",
+            Self::LgBump => &[
+                0x41, 0xa9, 0xe4, 0x67, 0x74, 0x4d, 0x1d, 0x1b, 0xa4, 0x29, 0xf2, 0xec, 0xea, 0x65,
+                0x52, 0x79,
+            ],
+        }
+    }
+}
+impl fmt::Display for TrailingTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::SeAndroidEnforce => "SEANDROIDENFORCE",
+            Self::LgBump => "LG bump",
+        })
+    }
+}
+
+/// Reads a known vendor tag right after the image data, which ends at `image_size`,
+/// e.g. [`Header::total_size`](crate::Header::total_size).
+///
+/// Returns `None` if the reader ends there or the bytes aren't a known tag. Keep the
+/// tag when repacking, since some bootloaders check for it.
+pub fn read_trailing_tag<R: Read + Seek>(
+    reader: &mut R,
+    image_size: usize,
+) -> io::Result<Option<TrailingTag>> {
+    reader.seek(SeekFrom::Start(image_size as u64))?;
+    let mut bytes = Vec::with_capacity(16);
+    reader.take(16).read_to_end(&mut bytes)?;
+    Ok([TrailingTag::SeAndroidEnforce, TrailingTag::LgBump]
+        .into_iter()
+        .find(|tag| bytes == tag.bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        tests::{build_image, header_v0},
+        Header, HeaderV0Versioned,
+    };
+
+    #[test]
+    fn samsung_tag() {
+        let mut hdr = header_v0(HeaderV0Versioned::V0);
+        hdr.kernel_size = 3000;
+        hdr.ramdisk_size = 100;
+        let hdr = Header::V0(hdr);
+        let mut image = build_image(&hdr);
+        assert_eq!(hdr.total_size(), image.len());
+        assert_eq!(
+            read_trailing_tag(&mut Cursor::new(&image), hdr.total_size()).unwrap(),
+            None
+        );
+
+        image.extend(b"SEANDROIDENFORCE");
+        let tag = read_trailing_tag(&mut Cursor::new(&image), hdr.total_size()).unwrap();
+        assert_eq!(tag, Some(TrailingTag::SeAndroidEnforce));
+        assert_eq!(tag.unwrap().to_string(), "SEANDROIDENFORCE");
+
+        image.truncate(image.len() - 1);
+        assert_eq!(
+            read_trailing_tag(&mut Cursor::new(&image), hdr.total_size()).unwrap(),
+            None
+        );
+    }
+}
@@ -1,11 +1,34 @@
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
 use binrw::{binrw, BinRead, BinWrite};
 
-// TODO: pretty section layout using asciiflow.com
+use crate::{take_until_null, Error, Header, PairError, Region};
 
 /// Android vendor boot image header version 3 and 4
+///
+/// ## Section layout
+///
+/// ```text
+/// ┌────────────────────────────┐
+/// │vendor boot image header    │
+/// │+ padding to page size      │
+/// ├────────────────────────────┤
+/// │vendor ramdisk              │
+/// │+ padding to page size      │
+/// ├────────────────────────────┤
+/// │dtb                         │
+/// │+ padding to page size      │
+/// ├────────────────────────────┤
+/// │vendor ramdisk table (v4)   │
+/// │+ padding to page size      │
+/// ├────────────────────────────┤
+/// │bootconfig (v4)             │
+/// │+ padding to page size      │
+/// └────────────────────────────┘
+/// ```
 #[binrw]
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
-#[brw(magic = b"VNDRBOOT")]
+#[brw(little, magic = b"VNDRBOOT")]
 #[br(assert(header_size == self.header_size()))]
 pub struct VendorHeader {
     #[br(temp)]
@@ -39,7 +62,18 @@ pub struct VendorHeader {
     pub v4: Option<VendorHeaderV4>,
 }
 impl VendorHeader {
+    /// Vendor boot image magic
+    pub const MAGIC: &'static [u8; 8] = b"VNDRBOOT";
+    /// Largest vendor ramdisk table entry count accepted by
+    /// [`read_ramdisk_table`](Self::read_ramdisk_table)
+    pub const MAX_RAMDISK_COUNT: u32 = 1024;
+
+    /// Parses an Android vendor boot image header from a reader.
+    pub fn parse<R: std::io::Read + std::io::Seek>(reader: &mut R) -> Result<Self, Error> {
+        Ok(Self::read(reader)?)
+    }
     /// Returns the vendor boot image header's version number.
+    #[must_use]
     pub fn header_version(&self) -> u32 {
         if self.v4.is_some() {
             4
@@ -54,13 +88,214 @@ impl VendorHeader {
             2112
         }
     }
+    /// Returns a copy of the header converted to version 3 or 4.
+    ///
+    /// Upgrading to version 4 adds zeroed v4 fields. Downgrading to version 3 fails
+    /// with [`Error::LossyDowngrade`] if any v4 field is non-zero.
+    pub fn to_version(&self, target: u32) -> Result<VendorHeader, Error> {
+        let v4 = match target {
+            3 => {
+                if self
+                    .v4
+                    .as_ref()
+                    .is_some_and(|v4| *v4 != VendorHeaderV4::default())
+                {
+                    return Err(Error::LossyDowngrade { version: target });
+                }
+                None
+            }
+            4 => Some(self.v4.clone().unwrap_or_default()),
+            version => return Err(Error::UnknownHeaderVersion { version }),
+        };
+        Ok(VendorHeader { v4, ..self.clone() })
+    }
+    /// Returns the length of the header in bytes, excluding padding.
+    ///
+    /// This is 2112 or 2128 bytes for versions 3 and 4.
+    #[must_use]
+    pub fn header_len(&self) -> usize {
+        self.header_size() as usize
+    }
+    fn get_padding(&self, size: usize) -> usize {
+        // self.page_size must be a power of two
+        let page_size = self.page_size as usize;
+        (page_size - (size & (page_size - 1))) & (page_size - 1)
+    }
+    /// Returns the vendor ramdisk's position in the vendor boot image.
+    #[must_use]
+    pub fn vendor_ramdisk_position(&self) -> usize {
+        self.header_len() + self.get_padding(self.header_len())
+    }
+    /// Returns the DTB's position in the vendor boot image.
+    #[must_use]
+    pub fn dtb_position(&self) -> usize {
+        self.vendor_ramdisk_position()
+            + self.vendor_ramdisk_size as usize
+            + self.get_padding(self.vendor_ramdisk_size as usize)
+    }
+    /// Returns the vendor ramdisk table's position in the vendor boot image.
+    ///
+    /// This returns `None` at version 3.
+    #[must_use]
+    pub fn vendor_ramdisk_table_position(&self) -> Option<usize> {
+        self.v4.as_ref()?;
+        Some(
+            self.dtb_position() + self.dtb_size as usize + self.get_padding(self.dtb_size as usize),
+        )
+    }
+    /// Returns the bootconfig's position in the vendor boot image.
+    ///
+    /// This returns `None` at version 3.
+    #[must_use]
+    pub fn bootconfig_position(&self) -> Option<usize> {
+        let table_size = self.v4.as_ref()?.vendor_ramdisk_table_size as usize;
+        Some(self.vendor_ramdisk_table_position()? + table_size + self.get_padding(table_size))
+    }
+    /// Returns the number of command line bytes in use, up to the null terminator.
+    ///
+    /// This equals [`cmdline_capacity`](Self::cmdline_capacity) if the command line
+    /// isn't terminated.
+    #[must_use]
+    pub fn cmdline_used_len(&self) -> usize {
+        take_until_null(&*self.cmdline).len()
+    }
+    /// Returns the size of the command line field, which is 2048 bytes.
+    #[must_use]
+    pub fn cmdline_capacity(&self) -> usize {
+        self.cmdline.len()
+    }
+    /// Returns whether the vendor ramdisk is split into the fragments listed in the
+    /// vendor ramdisk table.
+    ///
+    /// This returns `false` at version 3, or if the table has no entries.
+    #[must_use]
+    pub fn uses_ramdisk_table(&self) -> bool {
+        self.ramdisk_count() > 0
+    }
+    /// Returns the number of entries in the vendor ramdisk table.
+    ///
+    /// This returns 0 at version 3, which has no table.
+    #[must_use]
+    pub fn ramdisk_count(&self) -> usize {
+        self.v4
+            .as_ref()
+            .map_or(0, |v4| v4.vendor_ramdisk_table_entry_num as usize)
+    }
+    /// Reads the vendor ramdisk table.
+    ///
+    /// Returns an empty vector at version 3. Fails if the entry count exceeds
+    /// [`MAX_RAMDISK_COUNT`](Self::MAX_RAMDISK_COUNT), an entry is smaller than
+    /// [`VendorRamdiskTableEntry::SIZE`] or the entries don't exactly fill the
+    /// declared table size.
+    pub fn read_ramdisk_table<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+    ) -> Result<Vec<VendorRamdiskTableEntry>, Error> {
+        let (Some(v4), Some(position)) = (&self.v4, self.vendor_ramdisk_table_position()) else {
+            return Ok(Vec::new());
+        };
+        let count = v4.vendor_ramdisk_table_entry_num;
+        if count > Self::MAX_RAMDISK_COUNT {
+            return Err(Error::TooManyRamdisks { count });
+        }
+        let entry_size = v4.vendor_ramdisk_table_entry_size;
+        if entry_size < VendorRamdiskTableEntry::SIZE {
+            return Err(Error::InvalidRamdiskTableEntrySize { size: entry_size });
+        }
+        let computed = u64::from(count) * u64::from(entry_size);
+        if computed != u64::from(v4.vendor_ramdisk_table_size) {
+            return Err(Error::TableSizeMismatch {
+                expected: v4.vendor_ramdisk_table_size,
+                computed,
+            });
+        }
 
-    // TODO: functions to return positions according to the `data` table in
-    // https://github.com/cfig/Android_boot_image_editor/blob/master/doc/layout.md
+        reader.seek(SeekFrom::Start(position as u64))?;
+        let mut table = Vec::new();
+        reader.take(computed).read_to_end(&mut table)?;
+        if table.len() as u64 != computed {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        table
+            .chunks_exact(entry_size as usize)
+            .map(|entry| Ok(VendorRamdiskTableEntry::read(&mut Cursor::new(entry))?))
+            .collect()
+    }
+    /// Reads the fragment of the vendor ramdisk described by a vendor ramdisk table
+    /// entry.
+    ///
+    /// Fails with [`Error::RamdiskFragmentOutOfRange`] if the fragment extends past
+    /// the vendor ramdisk.
+    pub fn read_ramdisk_fragment<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        entry: &VendorRamdiskTableEntry,
+    ) -> Result<Vec<u8>, Error> {
+        if u64::from(entry.ramdisk_offset) + u64::from(entry.ramdisk_size)
+            > u64::from(self.vendor_ramdisk_size)
+        {
+            return Err(Error::RamdiskFragmentOutOfRange {
+                offset: entry.ramdisk_offset,
+                size: entry.ramdisk_size,
+                vendor_ramdisk_size: self.vendor_ramdisk_size,
+            });
+        }
+        let position = self.vendor_ramdisk_position() as u64 + u64::from(entry.ramdisk_offset);
+        reader.seek(SeekFrom::Start(position))?;
+        let mut fragment = Vec::new();
+        reader
+            .take(entry.ramdisk_size.into())
+            .read_to_end(&mut fragment)?;
+        if fragment.len() != entry.ramdisk_size as usize {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(fragment)
+    }
+    /// Returns the regions of the vendor boot image, starting with the header.
+    ///
+    /// Regions with a size of zero are included.
+    #[must_use]
+    pub fn layout(&self) -> Vec<Region> {
+        let region = |name, offset: usize, size: usize| Region {
+            name,
+            offset: offset as u64,
+            size: size as u64,
+            padding: self.get_padding(size) as u64,
+        };
+        let mut regions = vec![
+            region("header", 0, self.header_len()),
+            region(
+                "vendor_ramdisk",
+                self.vendor_ramdisk_position(),
+                self.vendor_ramdisk_size as usize,
+            ),
+            region("dtb", self.dtb_position(), self.dtb_size as usize),
+        ];
+        if let (Some(v4), Some(table_position), Some(bootconfig_position)) = (
+            &self.v4,
+            self.vendor_ramdisk_table_position(),
+            self.bootconfig_position(),
+        ) {
+            regions.extend([
+                region(
+                    "vendor_ramdisk_table",
+                    table_position,
+                    v4.vendor_ramdisk_table_size as usize,
+                ),
+                region(
+                    "bootconfig",
+                    bootconfig_position,
+                    v4.bootconfig_size as usize,
+                ),
+            ]);
+        }
+        regions
+    }
 }
 
 /// V4-specific fields of the Android vendor boot image header
-#[derive(BinRead, BinWrite, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(BinRead, BinWrite, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct VendorHeaderV4 {
     /// Vendor ramdisk table size
     pub vendor_ramdisk_table_size: u32,
@@ -71,3 +306,299 @@ pub struct VendorHeaderV4 {
     /// Bootconfig size
     pub bootconfig_size: u32,
 }
+
+/// Entry of the vendor ramdisk table (v4), describing a fragment of the vendor
+/// ramdisk
+#[derive(BinRead, BinWrite, Clone, Debug, PartialEq, Eq, Hash)]
+#[brw(little)]
+pub struct VendorRamdiskTableEntry {
+    /// Size of the fragment
+    pub ramdisk_size: u32,
+    /// Offset of the fragment in the vendor ramdisk
+    pub ramdisk_offset: u32,
+    /// Type of the fragment: 0 for none, 1 for platform, 2 for recovery or 3 for
+    /// DLKM
+    pub ramdisk_type: u32,
+    /// Name of the fragment
+    pub ramdisk_name: [u8; 32],
+    /// Hardware identifiers of the boards the fragment applies to
+    pub board_id: [u32; 16],
+}
+impl VendorRamdiskTableEntry {
+    /// Size of an entry in bytes, as written by `mkbootimg`
+    pub const SIZE: u32 = 108;
+
+    /// Returns the fragment's name, trimmed at its null terminator.
+    #[must_use]
+    pub fn name(&self) -> &[u8] {
+        take_until_null(&self.ramdisk_name)
+    }
+}
+
+/// Checks that a boot image and a vendor boot image can be booted together, as in a
+/// GKI setup.
+///
+/// The boot image must be version 3 or 4, which splits the vendor-specific parts into
+/// the vendor boot image. Either vendor boot version may be paired with either boot
+/// version. The page sizes may differ: the boot image always uses 4096-byte pages,
+/// while the vendor boot image has its own page size.
+pub fn validate_pair(boot: &Header, _vendor: &VendorHeader) -> Result<(), PairError> {
+    let version = boot.header_version();
+    if !(3..=4).contains(&version) {
+        return Err(PairError::BootVersion { version });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn vendor_header(v4: Option<VendorHeaderV4>) -> VendorHeader {
+        VendorHeader {
+            page_size: 4096,
+            kernel_addr: 0x10008000,
+            ramdisk_addr: 0x11000000,
+            vendor_ramdisk_size: 0,
+            cmdline: Box::new([0; 2048]),
+            tags_addr: 0x10000100,
+            board_name: [0; 16],
+            dtb_size: 0,
+            dtb_addr: 0x11f00000,
+            v4,
+        }
+    }
+
+    #[test]
+    fn validate_pair() {
+        use crate::tests::{header_v0, header_v3};
+
+        let mut boot = header_v3(0, 0);
+        let mut vendor = vendor_header(None);
+        super::validate_pair(&Header::V3(boot.clone()), &vendor).unwrap();
+        boot.v4_signature_size = Some(4096);
+        vendor.v4 = Some(VendorHeaderV4::default());
+        super::validate_pair(&Header::V3(boot.clone()), &vendor).unwrap();
+
+        // mkbootimg's default vendor boot page size
+        vendor.page_size = 2048;
+        super::validate_pair(&Header::V3(boot), &vendor).unwrap();
+        let boot = Header::V0(header_v0(crate::HeaderV0Versioned::V0));
+        assert_eq!(
+            super::validate_pair(&boot, &vendor),
+            Err(PairError::BootVersion { version: 0 })
+        );
+    }
+
+    #[test]
+    fn header_len() {
+        for (hdr, len) in [
+            (vendor_header(None), 2112),
+            (
+                vendor_header(Some(VendorHeaderV4 {
+                    vendor_ramdisk_table_size: 0,
+                    vendor_ramdisk_table_entry_num: 0,
+                    vendor_ramdisk_table_entry_size: 0,
+                    bootconfig_size: 0,
+                })),
+                2128,
+            ),
+        ] {
+            assert_eq!(hdr.header_len(), len);
+            let mut buf = std::io::Cursor::new(Vec::new());
+            hdr.write_le(&mut buf).unwrap();
+            assert_eq!(buf.into_inner().len(), len);
+        }
+    }
+
+    /// Builds a v4 vendor boot image with a vendor ramdisk of `ramdisk` and a ramdisk
+    /// table of `entries`.
+    fn image_with_table(
+        ramdisk: &[u8],
+        entries: &[VendorRamdiskTableEntry],
+    ) -> (VendorHeader, Vec<u8>) {
+        let mut hdr = vendor_header(Some(VendorHeaderV4 {
+            vendor_ramdisk_table_size: entries.len() as u32 * VendorRamdiskTableEntry::SIZE,
+            vendor_ramdisk_table_entry_num: entries.len() as u32,
+            vendor_ramdisk_table_entry_size: VendorRamdiskTableEntry::SIZE,
+            bootconfig_size: 0,
+        }));
+        hdr.vendor_ramdisk_size = ramdisk.len() as u32;
+        let mut image = Cursor::new(Vec::new());
+        hdr.write(&mut image).unwrap();
+        image.get_mut().resize(hdr.vendor_ramdisk_position(), 0);
+        image.get_mut().extend(ramdisk);
+        image
+            .get_mut()
+            .resize(hdr.vendor_ramdisk_table_position().unwrap(), 0);
+        image.set_position(image.get_ref().len() as u64);
+        for entry in entries {
+            entry.write(&mut image).unwrap();
+        }
+        (hdr, image.into_inner())
+    }
+
+    #[test]
+    fn read_ramdisk_table() {
+        let mut name = [0; 32];
+        name[..4].copy_from_slice(b"dlkm");
+        let entries = [
+            VendorRamdiskTableEntry {
+                ramdisk_size: 100,
+                ramdisk_offset: 0,
+                ramdisk_type: 1,
+                ramdisk_name: [0; 32],
+                board_id: [0; 16],
+            },
+            VendorRamdiskTableEntry {
+                ramdisk_size: 200,
+                ramdisk_offset: 100,
+                ramdisk_type: 3,
+                ramdisk_name: name,
+                board_id: [7; 16],
+            },
+        ];
+        let ramdisk: Vec<u8> = [[1; 100].as_slice(), &[2; 200]].concat();
+        let (hdr, image) = image_with_table(&ramdisk, &entries);
+        let table = hdr.read_ramdisk_table(&mut Cursor::new(&image)).unwrap();
+        assert_eq!(table, entries);
+        assert_eq!(table[1].name(), b"dlkm");
+        assert_eq!(
+            hdr.read_ramdisk_fragment(&mut Cursor::new(&image), &table[0])
+                .unwrap(),
+            [1; 100]
+        );
+        assert_eq!(
+            hdr.read_ramdisk_fragment(&mut Cursor::new(&image), &table[1])
+                .unwrap(),
+            [2; 200]
+        );
+
+        let mut entry = table[1].clone();
+        entry.ramdisk_offset = 101;
+        assert!(matches!(
+            hdr.read_ramdisk_fragment(&mut Cursor::new(&image), &entry),
+            Err(Error::RamdiskFragmentOutOfRange {
+                offset: 101,
+                size: 200,
+                vendor_ramdisk_size: 300
+            })
+        ));
+
+        assert!(vendor_header(None)
+            .read_ramdisk_table(&mut Cursor::new(&image))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn ramdisk_table_size_mismatch() {
+        let (mut hdr, image) = image_with_table(&[], &[]);
+        let v4 = hdr.v4.as_mut().unwrap();
+        v4.vendor_ramdisk_table_entry_num = 3;
+        v4.vendor_ramdisk_table_size = 2 * VendorRamdiskTableEntry::SIZE;
+        assert!(matches!(
+            hdr.read_ramdisk_table(&mut Cursor::new(&image)),
+            Err(Error::TableSizeMismatch {
+                expected: 216,
+                computed: 324
+            })
+        ));
+
+        let v4 = hdr.v4.as_mut().unwrap();
+        v4.vendor_ramdisk_table_entry_num = u32::MAX;
+        assert!(matches!(
+            hdr.read_ramdisk_table(&mut Cursor::new(&image)),
+            Err(Error::TooManyRamdisks { count: u32::MAX })
+        ));
+    }
+
+    #[test]
+    fn to_version() {
+        let v3 = vendor_header(None);
+        let v4 = v3.to_version(4).unwrap();
+        assert_eq!(v4.header_version(), 4);
+        assert_eq!(v4.v4, Some(VendorHeaderV4::default()));
+        assert_eq!(v4.to_version(3).unwrap(), v3);
+        assert_eq!(v3.to_version(3).unwrap(), v3);
+
+        let mut v4 = v4;
+        v4.v4.as_mut().unwrap().vendor_ramdisk_table_size = 108;
+        assert!(matches!(
+            v4.to_version(3),
+            Err(Error::LossyDowngrade { version: 3 })
+        ));
+        assert!(matches!(
+            v3.to_version(2),
+            Err(Error::UnknownHeaderVersion { version: 2 })
+        ));
+    }
+
+    #[test]
+    fn cmdline_used_len() {
+        let mut hdr = vendor_header(None);
+        assert_eq!(hdr.cmdline_capacity(), 2048);
+        assert_eq!(hdr.cmdline_used_len(), 0);
+        hdr.cmdline[..13].copy_from_slice(b"console=ttyS0");
+        assert_eq!(hdr.cmdline_used_len(), 13);
+        hdr.cmdline.fill(b'a');
+        assert_eq!(hdr.cmdline_used_len(), 2048);
+    }
+
+    #[test]
+    fn ramdisk_table() {
+        let v3 = vendor_header(None);
+        assert!(!v3.uses_ramdisk_table());
+        assert_eq!(v3.ramdisk_count(), 0);
+
+        let mut v4 = VendorHeaderV4 {
+            vendor_ramdisk_table_size: 0,
+            vendor_ramdisk_table_entry_num: 0,
+            vendor_ramdisk_table_entry_size: 108,
+            bootconfig_size: 0,
+        };
+        let empty = vendor_header(Some(v4.clone()));
+        assert!(!empty.uses_ramdisk_table());
+        assert_eq!(empty.ramdisk_count(), 0);
+
+        v4.vendor_ramdisk_table_entry_num = 3;
+        v4.vendor_ramdisk_table_size = 3 * 108;
+        let split = vendor_header(Some(v4));
+        assert!(split.uses_ramdisk_table());
+        assert_eq!(split.ramdisk_count(), 3);
+    }
+
+    #[test]
+    fn layout_v4() {
+        let mut hdr = vendor_header(Some(VendorHeaderV4 {
+            vendor_ramdisk_table_size: 2 * 108,
+            vendor_ramdisk_table_entry_num: 2,
+            vendor_ramdisk_table_entry_size: 108,
+            bootconfig_size: 30,
+        }));
+        hdr.vendor_ramdisk_size = 10000;
+        hdr.dtb_size = 5000;
+
+        let offsets = hdr
+            .layout()
+            .iter()
+            .map(|region| (region.name, region.offset, region.size, region.padding))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            offsets,
+            [
+                ("header", 0, 2128, 4096 - 2128),
+                ("vendor_ramdisk", 0x1000, 10000, 0x3000 - 10000),
+                ("dtb", 0x4000, 5000, 0x2000 - 5000),
+                ("vendor_ramdisk_table", 0x6000, 216, 0x1000 - 216),
+                ("bootconfig", 0x7000, 30, 0x1000 - 30),
+            ]
+        );
+
+        hdr.v4 = None;
+        assert_eq!(hdr.header_len(), 2112);
+        assert_eq!(hdr.layout().len(), 3);
+        assert_eq!(hdr.layout()[0].size, 2112);
+        assert_eq!(hdr.vendor_ramdisk_table_position(), None);
+    }
+}
@@ -1,7 +1,9 @@
-use std::fmt;
+use std::{fmt, str::FromStr};
 
 use binrw::{BinRead, BinWrite};
 
+use crate::ParseOsVersionError;
+
 /// OS version and patch level
 ///
 /// # Bitwise format
@@ -21,13 +23,20 @@ impl OsVersionPatch {
         Self((version.0 << 11) + patch.0 as u32)
     }
     /// Returns the version part.
+    #[must_use]
     pub fn version(self) -> OsVersion {
         OsVersion(self.0 >> 11)
     }
     /// Returns the patch part.
+    #[must_use]
     pub fn patch(self) -> OsPatch {
         OsPatch((self.0 & 0x7ff) as u16)
     }
+    /// Returns `true` if neither the version nor the patch level is set.
+    #[must_use]
+    pub fn is_unset(self) -> bool {
+        self.0 == 0
+    }
 }
 
 impl fmt::Debug for OsVersionPatch {
@@ -50,22 +59,46 @@ impl OsPatch {
         Self(((year - 2000) << 4) + month as u16)
     }
     /// Returns the year.
+    #[must_use]
     pub fn year(self) -> u16 {
         // Highest 12 bits indicate year
         (self.0 >> 4) + 2000
     }
     /// Returns the month.
+    #[must_use]
     pub fn month(self) -> u8 {
         // Lowest 4 bits indicate month
         (self.0 & 0xf) as u8
     }
 }
 
+/// Displays as `(none)` when the month is zero, which isn't a valid patch level.
 impl fmt::Display for OsPatch {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.month() == 0 {
+            return f.write_str("(none)");
+        }
         write!(f, "{}-{:02}", self.year(), self.month())
     }
 }
+/// Parses `YYYY-MM`, with a year in 2000-2127 and a month in 1-12.
+impl FromStr for OsPatch {
+    type Err = ParseOsVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseOsVersionError {
+            input: s.to_owned(),
+            expected: "YYYY-MM with a year in 2000-2127 and a month in 1-12",
+        };
+        let (year, month) = s.split_once('-').ok_or_else(err)?;
+        let year = year.parse().ok().filter(|year| (2000..2128).contains(year));
+        let month = month.parse().ok().filter(|month| (1..=12).contains(month));
+        match (year, month) {
+            (Some(year), Some(month)) => Ok(Self::new(year, month)),
+            _ => Err(err()),
+        }
+    }
+}
 impl fmt::Debug for OsPatch {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt::Display::fmt(self, f)
@@ -87,6 +120,7 @@ impl OsVersion {
         Self(((a as u32) << 14) | ((b as u32) << 7) | c as u32)
     }
     /// Returns the version parts.
+    #[must_use]
     pub fn version_parts(self) -> (u8, u8, u8) {
         let x = self.0;
         let a = x >> 14; // Top 7 bits
@@ -102,6 +136,33 @@ impl fmt::Display for OsVersion {
         write!(f, "{a}.{b}.{c}")
     }
 }
+/// Parses `A.B.C`, like `mkbootimg`. Missing parts are zero and each part must be
+/// below 128.
+impl FromStr for OsVersion {
+    type Err = ParseOsVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseOsVersionError {
+            input: s.to_owned(),
+            expected: "A.B.C with each part below 128",
+        };
+        let mut parts = [0; 3];
+        let mut split = s.split('.');
+        for part in &mut parts {
+            if let Some(digits) = split.next() {
+                *part = digits
+                    .parse()
+                    .ok()
+                    .filter(|part| *part < 128)
+                    .ok_or_else(err)?;
+            }
+        }
+        if split.next().is_some() {
+            return Err(err());
+        }
+        Ok(Self::new(parts[0], parts[1], parts[2]))
+    }
+}
 impl fmt::Debug for OsVersion {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt::Display::fmt(self, f)
@@ -118,4 +179,29 @@ fn test() {
     assert_eq!(vp, OsVersionPatch::new(vp.version(), vp.patch()));
     assert_eq!(vp.version(), OsVersion::new(12, 0, 0));
     assert_eq!(vp.patch(), OsPatch::new(2024, 6));
+    assert!(!vp.is_unset());
+}
+
+#[cfg(test)]
+#[test]
+fn test_unset() {
+    let vp = OsVersionPatch(0);
+    assert!(vp.is_unset());
+    assert_eq!(format!("{vp:?}"), "OsVersionPatch(0.0.0, (none))");
+    assert_eq!(OsPatch::new(2024, 0).to_string(), "(none)");
+}
+
+#[cfg(test)]
+#[test]
+fn test_from_str() {
+    assert_eq!("13.0.0".parse(), Ok(OsVersion::new(13, 0, 0)));
+    assert_eq!("11".parse(), Ok(OsVersion::new(11, 0, 0)));
+    assert!("1.2.3.4".parse::<OsVersion>().is_err());
+    assert!("128.0.0".parse::<OsVersion>().is_err());
+    assert!("13.x".parse::<OsVersion>().is_err());
+
+    assert_eq!("2024-12".parse(), Ok(OsPatch::new(2024, 12)));
+    assert!("2024-13".parse::<OsPatch>().is_err());
+    assert!("1999-01".parse::<OsPatch>().is_err());
+    assert!("2024".parse::<OsPatch>().is_err());
 }
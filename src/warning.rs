@@ -0,0 +1,43 @@
+use std::fmt;
+
+/// Non-fatal observation made while parsing a header
+///
+/// See [`Header::parse_with_warnings`](crate::Header::parse_with_warnings).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Warning {
+    /// The reserved bytes of a v3/v4 header aren't zero.
+    NonZeroReserved,
+    /// The kernel command line fills its field without a null terminator.
+    UnterminatedCmdline,
+    /// The board name fills its field without a null terminator.
+    UnterminatedBoardName,
+    /// The page size is a power of two but isn't one of 2048, 4096, 8192 or 16384,
+    /// which `mkbootimg` accepts.
+    UnusualPageSize {
+        /// Page size in bytes
+        page_size: u32,
+    },
+    /// Exactly one of the recovery DTBO/ACPIO size and address is zero (v1-v2).
+    InconsistentRecoveryDtbo {
+        /// Recovery DTBO/ACPIO size in bytes
+        size: u32,
+        /// Recovery DTBO/ACPIO physical load address
+        addr: u64,
+    },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NonZeroReserved => write!(f, "reserved header bytes aren't zero"),
+            Self::UnterminatedCmdline => write!(f, "command line isn't null-terminated"),
+            Self::UnterminatedBoardName => write!(f, "board name isn't null-terminated"),
+            Self::UnusualPageSize { page_size } => write!(f, "unusual page size {page_size}"),
+            Self::InconsistentRecoveryDtbo { size, addr } => write!(
+                f,
+                "recovery DTBO size {size} and address {addr:#x} disagree on whether it's present"
+            ),
+        }
+    }
+}
@@ -0,0 +1,411 @@
+use std::io::{self, Read, Write};
+
+use crate::{Error, ExtractedImage, Header, HeaderV0Versioned, Section, SectionKind};
+
+/// How [`write_image`] handles section data that doesn't match the header's sizes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SizePolicy {
+    /// Fail with [`Error::SectionSizeMismatch`]
+    Strict,
+    /// Update the header's sizes from the data before writing, like
+    /// [`Header::recompute_sizes`]
+    #[default]
+    Recompute,
+}
+
+/// Writes a whole boot image from a header and its sections, returning the inner
+/// writer.
+///
+/// With [`SizePolicy::Recompute`], the written header has the sizes of `image`'s
+/// sections instead of its own. Either way, a non-empty section the header's version
+/// doesn't have fails with [`Error::SectionNotInVersion`]. With the `hashing` feature,
+/// the `hash_digest` of a v0-v2 header is computed from `image` too, as by
+/// [`HeaderV0::compute_digest`](crate::HeaderV0::compute_digest). The image's
+/// [`trailing_tag`](ExtractedImage::trailing_tag), if any, is appended after the
+/// sections.
+pub fn write_image<W: Write>(
+    writer: W,
+    header: &Header,
+    image: &ExtractedImage,
+    policy: SizePolicy,
+) -> Result<W, Error> {
+    let mut header = header.clone();
+    match policy {
+        SizePolicy::Strict => check_sections_in_version(&header, image)?,
+        SizePolicy::Recompute => header.recompute_sizes(image)?,
+    }
+    #[cfg(feature = "hashing")]
+    if let Header::V0(hdr) = &mut header {
+        hdr.hash_digest = hdr.compute_digest(image);
+    }
+    let mut w = ImageWriter::new(writer);
+    w.write_header(&header)?;
+    for section in header.sections() {
+        let data = image.section(section.kind);
+        if section.size != 0 || !data.is_empty() {
+            w.write_section(section.kind, data)?;
+        }
+    }
+    let mut writer = w.finish()?;
+    if let Some(tag) = image.trailing_tag {
+        writer.write_all(tag.bytes())?;
+    }
+    Ok(writer)
+}
+
+impl Header {
+    /// Sets the header's section sizes to the lengths of `image`'s sections.
+    ///
+    /// Fails with [`Error::SectionNotInVersion`] if `image` has a non-empty section
+    /// the header's version doesn't have, or [`Error::SizeOverflow`] if a section is
+    /// larger than 4 GiB.
+    pub fn recompute_sizes(&mut self, image: &ExtractedImage) -> Result<(), Error> {
+        check_sections_in_version(self, image)?;
+        let size = |kind| u32::try_from(image.section(kind).len()).map_err(|_| Error::SizeOverflow);
+        match self {
+            Self::V0(hdr) => {
+                hdr.kernel_size = size(SectionKind::Kernel)?;
+                hdr.ramdisk_size = size(SectionKind::Ramdisk)?;
+                hdr.second_bootloader_size = size(SectionKind::Second)?;
+                match &mut hdr.versioned {
+                    HeaderV0Versioned::V0 => {}
+                    HeaderV0Versioned::V1 {
+                        recovery_dtbo_size, ..
+                    } => *recovery_dtbo_size = size(SectionKind::RecoveryDtbo)?,
+                    HeaderV0Versioned::V2 {
+                        recovery_dtbo_size,
+                        dtb_size,
+                        ..
+                    } => {
+                        *recovery_dtbo_size = size(SectionKind::RecoveryDtbo)?;
+                        *dtb_size = size(SectionKind::Dtb)?;
+                    }
+                }
+            }
+            Self::V3(hdr) => {
+                hdr.kernel_size = size(SectionKind::Kernel)?;
+                hdr.ramdisk_size = size(SectionKind::Ramdisk)?;
+                if hdr.v4_signature_size.is_some() {
+                    hdr.v4_signature_size = Some(size(SectionKind::BootSignature)?);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_sections_in_version(header: &Header, image: &ExtractedImage) -> Result<(), Error> {
+    let version = header.header_version();
+    for kind in [
+        SectionKind::Second,
+        SectionKind::RecoveryDtbo,
+        SectionKind::Dtb,
+        SectionKind::BootSignature,
+    ] {
+        if !image.section(kind).is_empty() && !kind.in_version(version) {
+            return Err(Error::SectionNotInVersion { kind, version });
+        }
+    }
+    Ok(())
+}
+
+/// Writer for whole boot images that enforces section order and inserts padding
+///
+/// Write the header first, then each section in on-disk order. Empty sections may be
+/// skipped. Call [`finish`](Self::finish) to pad the image to the page size.
+///
+/// ```
+/// # use abootimg_oxide::{Header, ImageWriter};
+/// # fn f(hdr: &Header, kernel: &[u8], ramdisk: &[u8]) -> Result<(), abootimg_oxide::Error> {
+/// let mut w = ImageWriter::new(Vec::new());
+/// w.write_header(hdr)?;
+/// w.write_kernel(kernel)?;
+/// w.write_ramdisk(ramdisk)?;
+/// let image = w.finish()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ImageWriter<W> {
+    writer: W,
+    position: usize,
+    page_size: usize,
+    header_version: u32,
+    /// Sections not yet written, in reverse on-disk order. `None` until the header
+    /// is written.
+    pending: Option<Vec<Section>>,
+}
+impl<W: Write> ImageWriter<W> {
+    /// Creates a new `ImageWriter`.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            position: 0,
+            page_size: 0,
+            header_version: 0,
+            pending: None,
+        }
+    }
+    /// Returns the number of bytes written so far.
+    #[must_use]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+    /// Writes the boot image header.
+    ///
+    /// Fails without writing anything if [`Header::validate_for_write`] does.
+    pub fn write_header(&mut self, header: &Header) -> Result<(), Error> {
+        if self.pending.is_some() {
+            return Err(Error::HeaderOutOfOrder);
+        }
+        header.validate_for_write()?;
+        let mut buf = Vec::new();
+        header.write(&mut buf)?;
+        self.writer.write_all(&buf)?;
+        self.position = buf.len();
+        self.page_size = header.page_size();
+        self.header_version = header.header_version();
+        let mut sections = header.sections();
+        sections.reverse();
+        self.pending = Some(sections);
+        Ok(())
+    }
+    /// Writes a section, padding the previous section to the page size.
+    ///
+    /// The data must be exactly as long as the size declared in the header, and the
+    /// header's version must have the section.
+    pub fn write_section(&mut self, kind: SectionKind, data: &[u8]) -> Result<(), Error> {
+        let pending = self
+            .pending
+            .as_mut()
+            .ok_or(Error::SectionOutOfOrder { kind })?;
+        if !kind.in_version(self.header_version) {
+            return Err(Error::SectionNotInVersion {
+                kind,
+                version: self.header_version,
+            });
+        }
+        let index = pending
+            .iter()
+            .rposition(|section| section.kind == kind)
+            .ok_or(Error::SectionOutOfOrder { kind })?;
+        if pending[index + 1..].iter().any(|section| section.size != 0) {
+            return Err(Error::SectionOutOfOrder { kind });
+        }
+        let section = pending[index];
+        if data.len() != section.size as usize {
+            return Err(Error::SectionSizeMismatch {
+                kind,
+                expected: section.size,
+                actual: data.len(),
+            });
+        }
+        pending.truncate(index);
+
+        self.pad_to(section.position)?;
+        self.writer.write_all(data)?;
+        self.position += data.len();
+        Ok(())
+    }
+    /// Writes the kernel.
+    pub fn write_kernel(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.write_section(SectionKind::Kernel, data)
+    }
+    /// Writes the ramdisk.
+    pub fn write_ramdisk(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.write_section(SectionKind::Ramdisk, data)
+    }
+    /// Writes the second stage bootloader.
+    pub fn write_second(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.write_section(SectionKind::Second, data)
+    }
+    /// Writes the recovery DTBO/ACPIO.
+    pub fn write_recovery_dtbo(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.write_section(SectionKind::RecoveryDtbo, data)
+    }
+    /// Writes the DTB.
+    pub fn write_dtb(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.write_section(SectionKind::Dtb, data)
+    }
+    /// Writes the boot signature.
+    pub fn write_boot_signature(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.write_section(SectionKind::BootSignature, data)
+    }
+    /// Pads the image to the page size and returns the inner writer.
+    ///
+    /// Fails if the header or a non-empty section wasn't written.
+    pub fn finish(mut self) -> Result<W, Error> {
+        let pending = self.pending.take().ok_or(Error::HeaderOutOfOrder)?;
+        if let Some(section) = pending.iter().rev().find(|section| section.size != 0) {
+            return Err(Error::MissingSection { kind: section.kind });
+        }
+        self.pad_to(self.position.next_multiple_of(self.page_size))?;
+        Ok(self.writer)
+    }
+    fn pad_to(&mut self, position: usize) -> io::Result<()> {
+        let padding = position - self.position;
+        io::copy(&mut io::repeat(0).take(padding as u64), &mut self.writer)?;
+        self.position = position;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::{header_v0, header_v3},
+        HeaderV0Versioned,
+    };
+
+    #[test]
+    fn write_in_order() {
+        let mut hdr = header_v0(HeaderV0Versioned::V0);
+        hdr.kernel_size = 3000;
+        hdr.ramdisk_size = 10;
+        let hdr = Header::V0(hdr);
+
+        let mut w = ImageWriter::new(Vec::new());
+        w.write_header(&hdr).unwrap();
+        w.write_kernel(&[SectionKind::Kernel as u8 + 1; 3000])
+            .unwrap();
+        w.write_ramdisk(&[SectionKind::Ramdisk as u8 + 1; 10])
+            .unwrap();
+        assert_eq!(w.finish().unwrap(), crate::tests::build_image(&hdr));
+    }
+
+    #[test]
+    fn write_out_of_order() {
+        let mut hdr = header_v0(HeaderV0Versioned::V0);
+        hdr.kernel_size = 3000;
+        hdr.ramdisk_size = 10;
+        let hdr = Header::V0(hdr);
+
+        let mut w = ImageWriter::new(Vec::new());
+        assert!(matches!(
+            w.write_kernel(&[0; 3000]),
+            Err(Error::SectionOutOfOrder {
+                kind: SectionKind::Kernel
+            })
+        ));
+        w.write_header(&hdr).unwrap();
+        assert!(matches!(
+            w.write_ramdisk(&[0; 10]),
+            Err(Error::SectionOutOfOrder {
+                kind: SectionKind::Ramdisk
+            })
+        ));
+        assert!(matches!(
+            w.write_kernel(&[0; 5]),
+            Err(Error::SectionSizeMismatch { .. })
+        ));
+        w.write_kernel(&[0; 3000]).unwrap();
+        assert!(matches!(
+            w.write_kernel(&[0; 3000]),
+            Err(Error::SectionOutOfOrder { .. })
+        ));
+        assert!(matches!(
+            w.finish(),
+            Err(Error::MissingSection {
+                kind: SectionKind::Ramdisk
+            })
+        ));
+    }
+
+    #[test]
+    fn write_invalid_header() {
+        let mut hdr = header_v0(HeaderV0Versioned::V0);
+        hdr.page_size = 3000;
+        let mut w = ImageWriter::new(Vec::new());
+        assert!(matches!(
+            w.write_header(&Header::V0(hdr.clone())),
+            Err(Error::InvalidPageSize { page_size: 3000 })
+        ));
+        assert_eq!(w.position(), 0);
+
+        hdr.page_size = 2048;
+        hdr.cmdline_part_2.fill(b'a');
+        assert!(matches!(
+            w.write_header(&Header::V0(hdr)),
+            Err(Error::UnterminatedCmdline)
+        ));
+    }
+
+    #[test]
+    fn write_image_size_policy() {
+        let hdr = Header::V3(header_v3(3000, 10));
+        let image = ExtractedImage {
+            kernel: vec![SectionKind::Kernel as u8 + 1; 5000],
+            ramdisk: vec![SectionKind::Ramdisk as u8 + 1; 10],
+            ..Default::default()
+        };
+        assert!(matches!(
+            write_image(Vec::new(), &hdr, &image, SizePolicy::Strict),
+            Err(Error::SectionSizeMismatch {
+                kind: SectionKind::Kernel,
+                expected: 3000,
+                actual: 5000
+            })
+        ));
+
+        let written = write_image(Vec::new(), &hdr, &image, SizePolicy::Recompute).unwrap();
+        let expected = Header::V3(header_v3(5000, 10));
+        assert_eq!(written, crate::tests::build_image(&expected));
+        assert_eq!(
+            write_image(Vec::new(), &expected, &image, SizePolicy::Strict).unwrap(),
+            written
+        );
+
+        let image = ExtractedImage {
+            dtb: Some(vec![0; 100]),
+            ..image
+        };
+        for policy in [SizePolicy::Strict, SizePolicy::Recompute] {
+            assert!(matches!(
+                write_image(Vec::new(), &expected, &image, policy),
+                Err(Error::SectionNotInVersion {
+                    kind: SectionKind::Dtb,
+                    version: 3
+                })
+            ));
+        }
+    }
+
+    #[test]
+    fn trailing_tag_round_trip() {
+        let hdr = Header::V3(header_v3(3000, 10));
+        let mut image = crate::tests::build_image(&hdr);
+        image.extend(b"SEANDROIDENFORCE");
+
+        let extracted = hdr.extract_all(&mut std::io::Cursor::new(&image)).unwrap();
+        assert_eq!(
+            extracted.trailing_tag,
+            Some(crate::TrailingTag::SeAndroidEnforce)
+        );
+        let written = write_image(Vec::new(), &hdr, &extracted, SizePolicy::Strict).unwrap();
+        assert_eq!(written, image);
+
+        let extracted = ExtractedImage {
+            trailing_tag: None,
+            ..extracted
+        };
+        let written = write_image(Vec::new(), &hdr, &extracted, SizePolicy::Strict).unwrap();
+        assert_eq!(written, image[..hdr.total_size()]);
+    }
+
+    #[test]
+    fn write_missing_section_kind() {
+        let hdr = Header::V3(header_v3(3000, 10));
+        let mut w = ImageWriter::new(Vec::new());
+        w.write_header(&hdr).unwrap();
+        w.write_kernel(&[0; 3000]).unwrap();
+        assert!(matches!(
+            w.write_second(&[0; 100]),
+            Err(Error::SectionNotInVersion {
+                kind: SectionKind::Second,
+                version: 3
+            })
+        ));
+    }
+}
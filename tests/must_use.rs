@@ -0,0 +1,5 @@
+#[test]
+fn ignored_results_warn() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
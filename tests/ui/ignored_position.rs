@@ -0,0 +1,9 @@
+#![deny(unused_must_use)]
+
+use abootimg_oxide::Header;
+
+fn main() {
+    let hdr = Header::parse(&mut std::io::Cursor::new(Vec::new())).unwrap();
+    hdr.kernel_position();
+    hdr.sections();
+}
@@ -1,23 +1,33 @@
 use std::{
-    fs::{create_dir_all, File},
+    fmt,
+    fs::{self, create_dir_all, File},
     io::{self, stdout, BufReader, Read, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
+    process::ExitCode,
 };
 
-use abootimg_oxide::{Header, HeaderV0Versioned};
+use abootimg_oxide::{
+    detect_kernel_arch, detect_kernel_format, peek_magic, read_trailing_tag, sha256_file,
+    AvbFooter, Header, HeaderV0Versioned, Manifest, ParseOptions, SectionKind, SectionPaths,
+    VendorHeader, VendorRamdiskTableEntry,
+};
 use clap::{Parser, ValueEnum};
 
 /// Simple program to greet a person
+///
+/// Exits with 1 on I/O errors, 2 on invalid arguments, 3 if the image can't be parsed
+/// and 4 if the image is invalid.
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
     /// Path to the boot, recovery or vendor_boot image
-    #[arg(long = "boot_img")]
-    boot_img: PathBuf,
+    #[arg(long = "boot_img", required_unless_present_any = ["features", "version_info"])]
+    boot_img: Option<PathBuf>,
 
-    /// Output directory of the unpacked images
-    #[arg(long, default_value = "out")]
-    out: PathBuf,
+    /// Output directory of the unpacked images [default: the image's file name without
+    /// its extension, or with `_unpacked` appended if it has none]
+    #[arg(long)]
+    out: Option<PathBuf>,
 
     /// Text output format
     #[arg(value_enum, long, default_value_t = TextOutputFormat::Info)]
@@ -26,6 +36,54 @@ struct Args {
     /// Output null-terminated argument strings
     #[arg(short = '0', long)]
     null: bool,
+
+    /// With `--format mkbootimg`, give the load addresses as absolute offsets with
+    /// `--base 0x00000000`, like AOSP's `unpack_bootimg`. This is the default
+    #[arg(long)]
+    mkbootimg_raw: bool,
+
+    /// With `--format mkbootimg`, factor a `--base` out of the load addresses so the
+    /// kernel offset is `mkbootimg`'s default 0x00008000, falling back to a base of 0
+    /// if an address is below it
+    #[arg(long, conflicts_with = "mkbootimg_raw")]
+    mkbootimg_base: bool,
+
+    /// Treat warnings about the image as errors
+    #[arg(long)]
+    strict: bool,
+
+    /// Don't print any text output, only extract the image
+    #[arg(short, long)]
+    quiet: bool,
+
+    /// Write a section's raw bytes to stdout instead of extracting the image
+    #[arg(long, value_enum, value_name = "SECTION")]
+    extract: Option<ExtractSection>,
+
+    /// Print each non-empty section's name, offset and size in decimal and hex instead
+    /// of extracting the image, e.g. for carving with `dd`
+    #[arg(long, conflicts_with = "extract")]
+    offsets: bool,
+
+    /// Keep each extracted section's zero padding up to the page size
+    #[arg(long, conflicts_with = "extract")]
+    with_padding: bool,
+
+    /// Refuse to extract images with a section larger than this many bytes
+    #[arg(long, value_name = "BYTES", default_value_t = ParseOptions::DEFAULT_MAX_SECTION_SIZE)]
+    max_section_size: u64,
+
+    /// Print the SHA-256 digest of the whole image, like `sha256sum`, and exit
+    #[arg(long, conflicts_with = "extract")]
+    sha256: bool,
+
+    /// List the optional library features compiled into this build and exit
+    #[arg(long, exclusive = true)]
+    features: bool,
+
+    /// Print the supported header versions and compiled-in features and exit
+    #[arg(long, exclusive = true)]
+    version_info: bool,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
@@ -35,44 +93,288 @@ enum TextOutputFormat {
     /// Output shell-escaped (quoted) argument strings that can be used to
     /// reconstruct the boot image using `mkbootimg`
     Mkbootimg,
+    /// Output the header and section byte ranges as a JSON array, e.g. for carving
+    /// sections with `dd`
+    LayoutJson,
+    /// Output one sorted `key=value` line per header field, e.g. for comparing images
+    /// with `diff`
+    Flat,
 }
 
-fn main() {
-    let args = Args::parse();
-    let mut r = BufReader::new(File::open(args.boot_img).unwrap());
-    let hdr = Header::parse(&mut r).unwrap();
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum ExtractSection {
+    Kernel,
+    Ramdisk,
+    Second,
+    #[value(name = "recovery_dtbo")]
+    RecoveryDtbo,
+    Dtb,
+    #[value(name = "boot_signature")]
+    BootSignature,
+}
+impl From<ExtractSection> for SectionKind {
+    fn from(section: ExtractSection) -> Self {
+        match section {
+            ExtractSection::Kernel => Self::Kernel,
+            ExtractSection::Ramdisk => Self::Ramdisk,
+            ExtractSection::Second => Self::Second,
+            ExtractSection::RecoveryDtbo => Self::RecoveryDtbo,
+            ExtractSection::Dtb => Self::Dtb,
+            ExtractSection::BootSignature => Self::BootSignature,
+        }
+    }
+}
 
-    let kernel_out_path = args.out.join("kernel");
-    let ramdisk_out_path = args.out.join("ramdisk");
-    let second_out_path = args.out.join("second");
-    let recovery_dtbo_out_path = args.out.join("recovery_dtbo");
-    let dtb_out_path = args.out.join("dtb");
+#[derive(Debug)]
+enum CliError {
+    /// Reading the image or writing the output failed.
+    Io { context: String, err: io::Error },
+    /// The arguments are invalid in a way clap doesn't check.
+    Usage(String),
+    /// The image couldn't be parsed.
+    Parse(abootimg_oxide::Error),
+    /// The image was parsed, but is invalid.
+    Validation(String),
+}
+impl CliError {
+    /// Converts a library error, adding `context` to I/O errors. Other errors mean
+    /// the image is invalid.
+    fn from_lib(err: abootimg_oxide::Error, context: impl FnOnce() -> String) -> Self {
+        match err {
+            abootimg_oxide::Error::Io(err) => Self::Io {
+                context: context(),
+                err,
+            },
+            err => Self::Validation(err.to_string()),
+        }
+    }
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Self::Io { .. } => ExitCode::from(1),
+            Self::Usage(_) => ExitCode::from(2),
+            Self::Parse(_) => ExitCode::from(3),
+            Self::Validation(_) => ExitCode::from(4),
+        }
+    }
+}
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { context, err } => write!(f, "{context}: {err}"),
+            Self::Usage(message) => f.write_str(message),
+            Self::Parse(err) => write!(f, "failed to parse boot image: {err}"),
+            Self::Validation(message) => write!(f, "invalid boot image: {message}"),
+        }
+    }
+}
 
-    // Get the inner File, so copy_file_range can be used
+trait IoContext<T> {
+    fn context(self, context: impl FnOnce() -> String) -> Result<T, CliError>;
+}
+impl<T> IoContext<T> for io::Result<T> {
+    fn context(self, context: impl FnOnce() -> String) -> Result<T, CliError> {
+        self.map_err(|err| CliError::Io {
+            context: context(),
+            err,
+        })
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            err.exit_code()
+        }
+    }
+}
+
+fn run(args: Args) -> Result<(), CliError> {
+    if args.features {
+        for feature in abootimg_oxide::compiled_features() {
+            println!("{feature}");
+        }
+        return Ok(());
+    }
+    if args.version_info {
+        println!(
+            "boot image header versions: {:?}",
+            abootimg_oxide::SUPPORTED_BOOT_VERSIONS
+        );
+        println!(
+            "vendor boot image header versions: {:?}",
+            abootimg_oxide::SUPPORTED_VENDOR_VERSIONS
+        );
+        let features = abootimg_oxide::compiled_features();
+        if features.is_empty() {
+            println!("features: (none)");
+        } else {
+            println!("features: {}", features.join(", "));
+        }
+        return Ok(());
+    }
+    if (args.mkbootimg_base || args.mkbootimg_raw) && args.format != TextOutputFormat::Mkbootimg {
+        return Err(CliError::Usage(
+            "--mkbootimg-base and --mkbootimg-raw need --format mkbootimg".to_owned(),
+        ));
+    }
+    let boot_img = args
+        .boot_img
+        .as_deref()
+        .expect("--boot_img is required without --features or --version-info");
+    let out = match &args.out {
+        Some(out) => out.clone(),
+        None => {
+            let mut out = boot_img
+                .file_stem()
+                .unwrap_or(boot_img.as_os_str())
+                .to_owned();
+            // The stem of an image without an extension is the image itself
+            if boot_img.extension().is_none() {
+                out.push("_unpacked");
+            }
+            PathBuf::from(out)
+        }
+    };
+    let mut r = BufReader::new(
+        File::open(boot_img).context(|| format!("failed to open {}", boot_img.display()))?,
+    );
+    if args.sha256 {
+        let digest =
+            sha256_file(&mut r).context(|| format!("failed to read {}", boot_img.display()))?;
+        println!("{}  {}", hex(&digest), boot_img.display());
+        return Ok(());
+    }
+    if peek_magic(&mut r, 0)
+        .context(|| format!("failed to read {}", boot_img.display()))?
+        .starts_with(VendorHeader::MAGIC)
+    {
+        let hdr = VendorHeader::parse(&mut r).map_err(CliError::Parse)?;
+        // TODO: extract the rest of vendor boot images
+        if !matches!(args.format, TextOutputFormat::Info) {
+            return Err(CliError::Validation(
+                "vendor boot images only support --format info".to_owned(),
+            ));
+        }
+        let table = if hdr.uses_ramdisk_table() {
+            create_out_dir(&out)?;
+            hdr.read_ramdisk_table(&mut r).map_err(CliError::Parse)?
+        } else {
+            Vec::new()
+        };
+        let names = fragment_file_names(&table);
+        for (entry, name) in table.iter().zip(&names) {
+            let fragment = hdr.read_ramdisk_fragment(&mut r, entry).map_err(|err| {
+                CliError::from_lib(err, || format!("failed to read {}", boot_img.display()))
+            })?;
+            let path = out.join(name);
+            fs::write(&path, fragment).context(|| format!("failed to write {}", path.display()))?;
+        }
+        if !args.quiet {
+            print_vendor_info(&hdr);
+            for (i, name) in names.iter().enumerate() {
+                println!("vendor ramdisk fragment {i}: {name}");
+            }
+        }
+        return Ok(());
+    }
+    let (hdr, warnings) = Header::parse_with_warnings(&mut r).map_err(CliError::Parse)?;
+    for warning in &warnings {
+        eprintln!("warning: {warning}");
+    }
+    if args.strict && !warnings.is_empty() {
+        return Err(CliError::Validation(
+            "image has warnings and --strict was given".to_owned(),
+        ));
+    }
+    let image_len = r
+        .get_ref()
+        .metadata()
+        .context(|| format!("failed to read metadata of {}", boot_img.display()))?
+        .len();
+    hdr.validate(image_len)
+        .map_err(|err| CliError::Validation(err.to_string()))?;
+    if let Some(footer) = AvbFooter::read_from_end(&mut r).map_err(|err| match err {
+        abootimg_oxide::Error::Io(err) => CliError::Io {
+            context: format!("failed to read {}", boot_img.display()),
+            err,
+        },
+        err => CliError::Parse(err),
+    })? {
+        hdr.check_avb_footer(&footer, image_len)
+            .map_err(|err| CliError::Validation(err.to_string()))?;
+    }
+    hdr.check_section_sizes(args.max_section_size)
+        .map_err(|err| CliError::Validation(err.to_string()))?;
+
+    if args.offsets {
+        for section in hdr.sections() {
+            if section.size != 0 {
+                println!(
+                    "{} {} 0x{:x} {} 0x{:x}",
+                    section.kind, section.position, section.position, section.size, section.size
+                );
+            }
+        }
+        return Ok(());
+    }
+
+    if let Some(section) = args.extract {
+        let kind = SectionKind::from(section);
+        let range = hdr.section_range(kind).ok_or_else(|| {
+            CliError::Validation(format!(
+                "header version {} has no {kind} section",
+                hdr.header_version()
+            ))
+        })?;
+        // Rust's stdout never translates newlines, even on Windows, so binary data
+        // is written as-is
+        (|| {
+            r.seek(SeekFrom::Start(range.start))?;
+            let mut out = stdout().lock();
+            io::copy(&mut r.take(range.end - range.start), &mut out)?;
+            out.flush()
+        })()
+        .context(|| format!("failed to write {kind} to stdout"))?;
+        return Ok(());
+    }
+
+    let kernel_out_path = out.join("kernel");
+    let ramdisk_out_path = out.join("ramdisk");
+    let second_out_path = out.join("second");
+    let recovery_dtbo_out_path = out.join("recovery_dtbo");
+    let dtb_out_path = out.join("dtb");
+
+    // Get the inner File, so copy_file_range can be used. Every read below seeks
+    // first, so nothing left in the BufReader's buffer is needed.
     let r = r.get_mut();
 
-    create_dir_all(&args.out).unwrap();
+    create_out_dir(&out)?;
 
-    let mut extract_part = |pos: usize, size: u32, path: &Path| {
-        r.seek(SeekFrom::Start(pos as u64)).unwrap();
-        io::copy(&mut r.take(size as u64), &mut File::create(path).unwrap()).unwrap();
+    let mut extract_part = |kind: SectionKind, path: &Path| {
+        (|| {
+            let mut file = File::create(path)?;
+            if args.with_padding {
+                return hdr.extract_section_padded(r, kind, &mut file);
+            }
+            hdr.read_section_into(r, kind, &mut file)
+        })()
+        .map_err(|err| {
+            CliError::from_lib(err, || format!("failed to extract {}", path.display()))
+        })?;
+        Ok::<_, CliError>(())
     };
 
-    extract_part(hdr.kernel_position(), hdr.kernel_size(), &kernel_out_path);
-    extract_part(
-        hdr.ramdisk_position(),
-        hdr.ramdisk_size(),
-        &ramdisk_out_path,
-    );
+    if hdr.kernel_size() != 0 {
+        extract_part(SectionKind::Kernel, &kernel_out_path)?;
+    }
+    extract_part(SectionKind::Ramdisk, &ramdisk_out_path)?;
 
     match &hdr {
         Header::V0(v0) => {
             if v0.second_bootloader_size != 0 {
-                extract_part(
-                    v0.second_bootloader_position(),
-                    v0.second_bootloader_size,
-                    &second_out_path,
-                );
+                extract_part(SectionKind::Second, &second_out_path)?;
             }
             if let HeaderV0Versioned::V1 {
                 recovery_dtbo_size, ..
@@ -82,33 +384,41 @@ fn main() {
             } = v0.versioned
             {
                 if recovery_dtbo_size != 0 {
-                    extract_part(
-                        v0.recovery_dtbo_position(),
-                        recovery_dtbo_size,
-                        &recovery_dtbo_out_path,
-                    );
+                    extract_part(SectionKind::RecoveryDtbo, &recovery_dtbo_out_path)?;
                 }
             }
             if let HeaderV0Versioned::V2 { dtb_size, .. } = v0.versioned {
                 if dtb_size != 0 {
-                    extract_part(v0.dtb_position().unwrap(), dtb_size, &dtb_out_path);
+                    extract_part(SectionKind::Dtb, &dtb_out_path)?;
                 }
             }
         }
         Header::V3(v3) => {
-            if let Some(size) = v3.v4_signature_size.filter(|size| *size != 0) {
-                extract_part(
-                    v3.bootsig_position(),
-                    size,
-                    &args.out.join("boot_signature"),
-                );
+            if v3.v4_signature_size.is_some_and(|size| size != 0) {
+                extract_part(SectionKind::BootSignature, &out.join("boot_signature"))?;
             }
         }
     }
 
+    let trailing_tag = read_trailing_tag(r, hdr.total_size())
+        .context(|| format!("failed to read {}", boot_img.display()))?;
+    let manifest_path = out.join("manifest.json");
+    (|| {
+        let mut w = File::create(&manifest_path)?;
+        let manifest = Manifest {
+            trailing_tag,
+            ..Manifest::from_header(&hdr)
+        };
+        serde_json::to_writer_pretty(&mut w, &manifest)?;
+        writeln!(w)
+    })()
+    .context(|| format!("failed to write {}", manifest_path.display()))?;
+
+    if args.quiet {
+        return Ok(());
+    }
     match args.format {
         TextOutputFormat::Info => {
-            // TODO: vendor boot images
             println!("boot magic: ANDROID!");
             match &hdr {
                 Header::V0(v0) => {
@@ -126,12 +436,29 @@ fn main() {
                 }
                 Header::V3(v3) => {
                     println!("kernel_size: {}", v3.kernel_size);
-                    println!("ramdisk size: {}", v3.kernel_size);
+                    println!("ramdisk size: {}", v3.ramdisk_size);
                 }
             }
 
-            println!("os version: {}", hdr.osversionpatch().version());
-            println!("os patch level: {}", hdr.osversionpatch().patch());
+            if hdr.kernel_size() != 0 {
+                // Enough for the x86 bzImage magic at 0x202
+                let mut head = Vec::with_capacity(0x206);
+                (|| {
+                    r.seek(SeekFrom::Start(hdr.kernel_position() as u64))?;
+                    r.take(hdr.kernel_size().min(0x206) as u64)
+                        .read_to_end(&mut head)
+                })()
+                .context(|| format!("failed to read {}", boot_img.display()))?;
+                println!("kernel compression: {}", detect_kernel_format(&head));
+                println!("kernel arch: {}", detect_kernel_arch(&head));
+            }
+            if hdr.osversionpatch().is_unset() {
+                println!("os version: (unset)");
+                println!("os patch level: (unset)");
+            } else {
+                println!("os version: {}", hdr.osversionpatch().version());
+                println!("os patch level: {}", hdr.osversionpatch().patch());
+            }
             println!("boot image header version: {}", hdr.header_version());
             match &hdr {
                 Header::V0(v0) => {
@@ -142,6 +469,7 @@ fn main() {
                     print!("\nadditional command line args: ");
                     print_null_bytestring(&*v0.cmdline_part_2);
                     println!();
+                    println!("hash digest (id): {}", hex_digest(&v0.hash_digest));
                     match v0.versioned {
                         HeaderV0Versioned::V1 {
                             recovery_dtbo_size,
@@ -175,92 +503,218 @@ fn main() {
                     }
                 }
             }
+            if let Some(tag) = trailing_tag {
+                println!("trailing tag: {tag}");
+            }
         }
-        TextOutputFormat::Mkbootimg => {
-            let sep = if args.null { '\0' } else { ' ' };
-
-            print!(
-                "--header_version{sep}{}{sep}--os_version{sep}{}{sep}--os_patch_level{sep}{}",
-                hdr.header_version(),
-                hdr.osversionpatch().version(),
-                hdr.osversionpatch().patch()
-            );
-            {
-                // TODO: quote if out has whitespace
-                print!("{sep}--kernel{sep}");
-                stdout()
-                    .write_all(kernel_out_path.as_os_str().as_encoded_bytes())
-                    .ok();
-                print!("{sep}--ramdisk{sep}");
-                stdout()
-                    .write_all(ramdisk_out_path.as_os_str().as_encoded_bytes())
-                    .ok();
-                if let Header::V0(v0) = &hdr {
-                    if v0.second_bootloader_size != 0 {
-                        print!("{sep}--second{sep}");
-                        stdout()
-                            .write_all(second_out_path.as_os_str().as_encoded_bytes())
-                            .ok();
-                    }
-                    if let HeaderV0Versioned::V1 {
-                        recovery_dtbo_size, ..
-                    }
-                    | HeaderV0Versioned::V2 {
-                        recovery_dtbo_size, ..
-                    } = v0.versioned
-                    {
-                        if recovery_dtbo_size != 0 {
-                            print!("{sep}--recovery_dtbo{sep}");
-                            stdout()
-                                .write_all(recovery_dtbo_out_path.as_os_str().as_encoded_bytes())
-                                .ok();
-                        }
-                    }
-                    if let HeaderV0Versioned::V2 { dtb_size, .. } = v0.versioned {
-                        if dtb_size != 0 {
-                            print!("{sep}--dtb{sep}");
-                            stdout()
-                                .write_all(dtb_out_path.as_os_str().as_encoded_bytes())
-                                .ok();
-                        }
-                    }
-                }
+        TextOutputFormat::Flat => (|| {
+            let mut fields = flat_fields(&hdr);
+            fields.sort();
+            let mut out = stdout().lock();
+            for (key, value) in fields {
+                writeln!(out, "{key}={value}")?;
             }
-            if let Header::V0(v0) = &hdr {
-                print!("{sep}--pagesize{sep}0x{:08x}", hdr.page_size());
-                print!("{sep}--base{sep}0x{:08x}", 0);
-                print!("{sep}--kernel_offset{sep}0x{:08x}", v0.kernel_addr);
-                print!("{sep}--ramdisk_offset{sep}0x{:08x}", v0.ramdisk_addr);
-                print!(
-                    "{sep}--second_offset{sep}0x{:08x}",
-                    v0.second_bootloader_addr
-                );
-                print!("{sep}--tags_offset{sep}0x{:08x}", v0.tags_addr);
-                if let HeaderV0Versioned::V2 { dtb_addr, .. } = v0.versioned {
-                    print!(" --dtb_offset 0x{dtb_addr:016x}");
-                }
-                print!("{sep}--board{sep}");
-                if args.null {
-                    print_null_bytestring(&v0.board_name);
-                } else {
-                    print_escaped_null_bytestring(&v0.board_name);
+            Ok(())
+        })()
+        .context(|| "failed to write to stdout".to_owned())?,
+        TextOutputFormat::LayoutJson => (|| {
+            let mut out = stdout().lock();
+            serde_json::to_writer_pretty(&mut out, &hdr.layout())?;
+            writeln!(out)
+        })()
+        .context(|| "failed to write to stdout".to_owned())?,
+        TextOutputFormat::Mkbootimg => {
+            let sep: &[u8] = if args.null { b"\0" } else { b" " };
+            let base = if args.mkbootimg_base {
+                hdr.mkbootimg_base()
+            } else {
+                0
+            };
+            let mkbootimg_args = hdr.to_mkbootimg_args_with_base(&SectionPaths::in_dir(&out), base);
+            let q = shlex::bytes::Quoter::new();
+            let mut out = stdout().lock();
+            for (i, arg) in mkbootimg_args.iter().enumerate() {
+                if i != 0 {
+                    out.write_all(sep).ok();
                 }
-                print!("{sep}--cmdline{sep}");
-                let mut cmdline = v0.cmdline_part_1.to_vec();
-                cmdline.extend_from_slice(&*v0.cmdline_part_2);
+                let arg = arg.as_encoded_bytes();
                 if args.null {
-                    print_null_bytestring(&cmdline);
+                    out.write_all(arg).ok();
                 } else {
-                    print_escaped_null_bytestring(&cmdline);
+                    out.write_all(&q.quote(arg).unwrap()).ok();
                 }
             }
             if args.null {
-                print!("\0");
+                out.write_all(b"\0").ok();
             } else {
-                println!();
+                out.write_all(b"\n").ok();
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns every header field as a key and value for `--format flat`.
+///
+/// Byte string values are escaped, so each field stays on one line.
+fn flat_fields(hdr: &Header) -> Vec<(&'static str, String)> {
+    let osversionpatch = hdr.osversionpatch();
+    let mut fields = vec![
+        ("header_version", hdr.header_version().to_string()),
+        ("header_size", hdr.header_len().to_string()),
+        ("page_size", hdr.page_size().to_string()),
+        ("kernel_size", hdr.kernel_size().to_string()),
+        ("ramdisk_size", hdr.ramdisk_size().to_string()),
+        ("os_version", osversionpatch.version().to_string()),
+        ("os_patch_level", osversionpatch.patch().to_string()),
+    ];
+    match hdr {
+        Header::V0(v0) => {
+            fields.extend([
+                ("kernel_addr", format!("0x{:08x}", v0.kernel_addr)),
+                ("ramdisk_addr", format!("0x{:08x}", v0.ramdisk_addr)),
+                ("second_size", v0.second_bootloader_size.to_string()),
+                (
+                    "second_addr",
+                    format!("0x{:08x}", v0.second_bootloader_addr),
+                ),
+                ("tags_addr", format!("0x{:08x}", v0.tags_addr)),
+                ("board_name", escape(take_until_null(&v0.board_name))),
+                ("cmdline", escape(take_until_null(&*v0.cmdline_part_1))),
+                (
+                    "extra_cmdline",
+                    escape(take_until_null(&*v0.cmdline_part_2)),
+                ),
+                ("hash_digest", hex_digest(&v0.hash_digest)),
+            ]);
+            if let HeaderV0Versioned::V1 {
+                recovery_dtbo_size,
+                recovery_dtbo_addr,
+            }
+            | HeaderV0Versioned::V2 {
+                recovery_dtbo_size,
+                recovery_dtbo_addr,
+                ..
+            } = v0.versioned
+            {
+                fields.extend([
+                    ("recovery_dtbo_size", recovery_dtbo_size.to_string()),
+                    ("recovery_dtbo_addr", format!("0x{recovery_dtbo_addr:016x}")),
+                ]);
             }
+            if let HeaderV0Versioned::V2 {
+                dtb_size, dtb_addr, ..
+            } = v0.versioned
+            {
+                fields.extend([
+                    ("dtb_size", dtb_size.to_string()),
+                    ("dtb_addr", format!("0x{dtb_addr:016x}")),
+                ]);
+            }
+        }
+        Header::V3(v3) => {
+            fields.push(("cmdline", escape(take_until_null(&*v3.cmdline))));
+            if let Some(size) = v3.v4_signature_size {
+                fields.push(("boot_signature_size", size.to_string()));
+            }
+        }
+    }
+    fields
+}
+fn escape(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .flat_map(|b| std::ascii::escape_default(*b))
+        .map(char::from)
+        .collect()
+}
+
+/// Creates the output directory, failing if it's an existing file.
+fn create_out_dir(out: &Path) -> Result<(), CliError> {
+    if out.metadata().is_ok_and(|metadata| !metadata.is_dir()) {
+        return Err(CliError::Usage(format!(
+            "output directory {} is an existing file; pass another --out",
+            out.display()
+        )));
+    }
+    create_dir_all(out).context(|| format!("failed to create directory {}", out.display()))
+}
+
+/// Returns the file names of vendor ramdisk fragments: `vendor_ramdisk_<name>`, with
+/// characters other than ASCII alphanumerics, `-`, `_` and `.` replaced by `_`.
+///
+/// Empty names become `vendor_ramdisk_<index>`. Names already taken get `_<index>`
+/// suffixes until they're unique, so every fragment gets its own file.
+fn fragment_file_names(table: &[VendorRamdiskTableEntry]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::with_capacity(table.len());
+    for (i, entry) in table.iter().enumerate() {
+        let name: String = entry
+            .name()
+            .iter()
+            .map(|&b| match b {
+                b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' => char::from(b),
+                _ => '_',
+            })
+            .collect();
+        let mut name = if name.is_empty() {
+            format!("vendor_ramdisk_{i}")
+        } else {
+            format!("vendor_ramdisk_{name}")
+        };
+        while names.contains(&name) {
+            name = format!("{name}_{i}");
         }
+        names.push(name);
     }
+    names
+}
+
+fn print_vendor_info(hdr: &VendorHeader) {
+    println!("boot magic: VNDRBOOT");
+    println!("vendor boot image header version: {}", hdr.header_version());
+    println!("page size: {}", hdr.page_size);
+    println!("kernel load address: 0x{:08x}", hdr.kernel_addr);
+    println!("ramdisk load address: 0x{:08x}", hdr.ramdisk_addr);
+    println!("vendor ramdisk total size: {}", hdr.vendor_ramdisk_size);
+    print!("vendor command line args: ");
+    print_null_bytestring(&*hdr.cmdline);
+    println!();
+    println!("kernel tags load address: 0x{:08x}", hdr.tags_addr);
+    print!("product name: ");
+    print_null_bytestring(&hdr.board_name);
+    println!();
+    println!("vendor boot image header size: {}", hdr.header_len());
+    println!("dtb size: {}", hdr.dtb_size);
+    println!("dtb address: 0x{:016x}", hdr.dtb_addr);
+    if let Some(v4) = &hdr.v4 {
+        println!(
+            "vendor ramdisk table size: {}",
+            v4.vendor_ramdisk_table_size
+        );
+        println!(
+            "vendor ramdisk table entry num: {}",
+            v4.vendor_ramdisk_table_entry_num
+        );
+        println!(
+            "vendor ramdisk table entry size: {}",
+            v4.vendor_ramdisk_table_entry_size
+        );
+        println!("vendor bootconfig size: {}", v4.bootconfig_size);
+    }
+}
+
+/// Formats a header's hash digest as lowercase hex, leaving out the zero padding after
+/// a 20-byte SHA-1 digest.
+fn hex_digest(digest: &[u8; 32]) -> String {
+    let digest = if digest[20..] == [0; 12] {
+        &digest[..20]
+    } else {
+        digest
+    };
+    hex(digest)
+}
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
 }
 
 fn take_until_null(input: &[u8]) -> &[u8] {
@@ -269,12 +723,6 @@ fn take_until_null(input: &[u8]) -> &[u8] {
         None => input,
     }
 }
-fn print_escaped_null_bytestring(input: &[u8]) {
-    let q = shlex::bytes::Quoter::new();
-    stdout()
-        .write_all(&q.quote(take_until_null(input)).unwrap())
-        .ok();
-}
 fn print_null_bytestring(input: &[u8]) {
     stdout().write_all(take_until_null(input)).ok();
 }
@@ -0,0 +1,712 @@
+use std::{fs, io::Cursor, path::PathBuf, process::Command};
+
+use abootimg_oxide::Header;
+
+fn unpack_bootimg() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_unpack_bootimg"))
+}
+
+/// Returns a fresh directory for a test.
+fn test_dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Returns a v0 header with the kernel size, page size and kernel, ramdisk, second
+/// and tags load addresses given, padded to a page. Other fields are zero.
+fn v0_image(kernel_size: u32, page_size: u32, addrs: [u32; 4]) -> Vec<u8> {
+    let mut data = b"ANDROID!".to_vec();
+    data.extend(kernel_size.to_le_bytes());
+    let [kernel_addr, ramdisk_addr, second_addr, tags_addr] = addrs;
+    for field in [
+        kernel_addr,
+        0,
+        ramdisk_addr,
+        0,
+        second_addr,
+        tags_addr,
+        page_size,
+    ] {
+        data.extend(field.to_le_bytes());
+    }
+    data.resize((page_size as usize).max(1632), 0);
+    data
+}
+
+#[test]
+fn malformed_image_exit_code() {
+    let dir = test_dir("malformed_image_exit_code");
+    let image = dir.join("boot.img");
+    let mut data = b"ANDROID!".to_vec();
+    data.resize(0x28, 0);
+    data.extend(7u32.to_le_bytes());
+    data.resize(4096, 0);
+    fs::write(&image, data).unwrap();
+
+    let output = unpack_bootimg()
+        .arg("--boot_img")
+        .arg(&image)
+        .arg("--out")
+        .arg(dir.join("out"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn truncated_image_error() {
+    let dir = test_dir("truncated_image_error");
+    let image = dir.join("boot.img");
+    let mut data = b"ANDROID!".to_vec();
+    data.resize(500, 0);
+    fs::write(&image, data).unwrap();
+
+    let output = unpack_bootimg()
+        .arg("--boot_img")
+        .arg(&image)
+        .arg("--out")
+        .arg(dir.join("out"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "error: failed to parse boot image: image is 500 bytes, but the header needs 1632 bytes\n"
+    );
+}
+
+#[test]
+fn zero_page_size() {
+    let dir = test_dir("zero_page_size");
+    let image = dir.join("boot.img");
+    let mut data = v0_image(3000, 0, [0; 4]);
+    data.resize(4096, 0);
+    fs::write(&image, data).unwrap();
+
+    for args in [&[][..], &["--offsets"]] {
+        let output = unpack_bootimg()
+            .arg("--boot_img")
+            .arg(&image)
+            .arg("--out")
+            .arg(dir.join("out"))
+            .args(args)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            "error: failed to parse boot image: invalid page size 0\n"
+        );
+    }
+}
+
+#[test]
+fn features() {
+    let output = unpack_bootimg().arg("--features").output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    // unpack_bootimg writes manifest.json, which needs the serde feature
+    assert!(stdout.lines().any(|line| line == "serde"), "{stdout}");
+}
+
+#[test]
+fn version_info() {
+    let output = unpack_bootimg().arg("--version-info").output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("boot image header versions: 0..=4\n"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("vendor boot image header versions: 3..=4\n"));
+    assert!(stdout.contains("features: "));
+}
+
+#[test]
+fn extract_to_stdout() {
+    let dir = test_dir("extract_to_stdout");
+    let image = dir.join("boot.img");
+    // v0 header with a 2048-byte page size and a kernel containing newlines, which
+    // must not be translated
+    let kernel = b"line\nline\r\n\x00\xff\n".repeat(10);
+    let mut data = v0_image(kernel.len() as u32, 2048, [0; 4]);
+    data.extend(&kernel);
+    data.resize(4096, 0);
+    fs::write(&image, data).unwrap();
+
+    let output = unpack_bootimg()
+        .arg("--boot_img")
+        .arg(&image)
+        .args(["--extract", "kernel"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, kernel);
+
+    let output = unpack_bootimg()
+        .arg("--boot_img")
+        .arg(&image)
+        .args(["--extract", "dtb"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(4));
+}
+
+#[test]
+fn vendor_info() {
+    let dir = test_dir("vendor_info");
+    let image = dir.join("vendor_boot.img");
+    let mut data = b"VNDRBOOT".to_vec();
+    for field in [4, 4096, 0x10008000, 0x11000000, 0] {
+        data.extend(u32::to_le_bytes(field));
+    }
+    data.extend(b"console=ttyMSM0");
+    data.resize(data.len() + 2048 - 15, 0);
+    data.extend(0x10000100u32.to_le_bytes());
+    data.extend(b"board\0\0\0\0\0\0\0\0\0\0\0");
+    data.extend(2128u32.to_le_bytes());
+    data.extend(0u32.to_le_bytes());
+    data.extend(0x1_1f00_0000u64.to_le_bytes());
+    data.extend([0; 16]);
+    data.resize(4096, 0);
+    fs::write(&image, data).unwrap();
+
+    let output = unpack_bootimg()
+        .arg("--boot_img")
+        .arg(&image)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("vendor boot image header version: 4\n"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("kernel load address: 0x10008000\n"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("vendor command line args: console=ttyMSM0\n"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("product name: board\n"), "{stdout}");
+    assert!(
+        stdout.contains("dtb address: 0x000000011f000000\n"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("vendor bootconfig size: 0\n"), "{stdout}");
+}
+
+#[test]
+fn sha256() {
+    let dir = test_dir("sha256");
+    let image = dir.join("boot.img");
+    fs::write(&image, b"abc").unwrap();
+
+    let output = unpack_bootimg()
+        .arg("--boot_img")
+        .arg(&image)
+        .arg("--sha256")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  {}\n",
+            image.display()
+        )
+    );
+}
+
+#[test]
+fn hash_digest() {
+    let dir = test_dir("hash_digest");
+    let image = dir.join("boot.img");
+    let mut data = v0_image(0, 2048, [0; 4]);
+    data.splice(0x240..0x240 + 20, 0..20);
+    fs::write(&image, data).unwrap();
+
+    let output = unpack_bootimg()
+        .arg("--boot_img")
+        .arg(&image)
+        .arg("--out")
+        .arg(dir.join("out"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("hash digest (id): 000102030405060708090a0b0c0d0e0f10111213\n"),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn layout_json() {
+    let dir = test_dir("layout_json");
+    let image = dir.join("boot.img");
+    let mut data = v0_image(3000, 4096, [0; 4]);
+    data.resize(3 * 4096, 0);
+    fs::write(&image, &data).unwrap();
+
+    let output = unpack_bootimg()
+        .arg("--boot_img")
+        .arg(&image)
+        .arg("--out")
+        .arg(dir.join("out"))
+        .args(["--format", "layout-json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let layout: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let hdr = Header::parse(&mut Cursor::new(&data)).unwrap();
+    assert_eq!(layout[0]["name"], "header");
+    assert_eq!(layout[1]["name"], "kernel");
+    assert_eq!(layout[1]["offset"], hdr.kernel_position() as u64);
+    assert_eq!(layout[1]["size"], 3000);
+    assert_eq!(layout[1]["padding"], 1096);
+    assert_eq!(
+        fs::read(dir.join("out/kernel")).unwrap().len(),
+        layout[1]["size"].as_u64().unwrap() as usize
+    );
+}
+
+#[test]
+fn with_padding() {
+    let dir = test_dir("with_padding");
+    let image = dir.join("boot.img");
+    let mut data = v0_image(3000, 4096, [0; 4]);
+    data.resize(4096 + 3000, 0xaa);
+    data.resize(3 * 4096, 0);
+    fs::write(&image, &data).unwrap();
+
+    for (padded, len) in [(false, 3000), (true, 4096)] {
+        let out = dir.join(if padded { "padded" } else { "unpadded" });
+        let mut cmd = unpack_bootimg();
+        cmd.arg("--boot_img")
+            .arg(&image)
+            .arg("--out")
+            .arg(&out)
+            .arg("-q");
+        if padded {
+            cmd.arg("--with-padding");
+        }
+        assert!(cmd.status().unwrap().success());
+        let kernel = fs::read(out.join("kernel")).unwrap();
+        assert_eq!(kernel.len(), len);
+        assert_eq!(kernel[..3000], data[4096..7096]);
+        assert!(kernel[3000..].iter().all(|b| *b == 0));
+        assert_eq!(fs::read(out.join("ramdisk")).unwrap().len(), 0);
+    }
+}
+
+#[test]
+fn flat() {
+    let dir = test_dir("flat");
+    let image = dir.join("boot.img");
+    let mut data = v0_image(3000, 4096, [0; 4]);
+    data[0x40..][..10].copy_from_slice(b"line\nbreak");
+    data.resize(3 * 4096, 0);
+    fs::write(&image, &data).unwrap();
+
+    let output = unpack_bootimg()
+        .arg("--boot_img")
+        .arg(&image)
+        .arg("--out")
+        .arg(dir.join("out"))
+        .args(["--format", "flat"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+    assert!(lines.is_sorted(), "{stdout}");
+    assert!(lines.contains(&"kernel_size=3000"), "{stdout}");
+    assert!(lines.contains(&"page_size=4096"), "{stdout}");
+    assert!(lines.contains(&"cmdline=line\\nbreak"), "{stdout}");
+    assert!(lines.iter().all(|line| line.contains('=')));
+}
+
+#[test]
+fn zero_kernel() {
+    let dir = test_dir("zero_kernel");
+    let image = dir.join("boot.img");
+    let mut data = v0_image(0, 4096, [0; 4]);
+    // Ramdisk size
+    data[0x10..][..4].copy_from_slice(&100u32.to_le_bytes());
+    data.resize(4096 + 100, 0xaa);
+    data.resize(2 * 4096, 0);
+    fs::write(&image, &data).unwrap();
+
+    let out = dir.join("out");
+    let output = unpack_bootimg()
+        .arg("--boot_img")
+        .arg(&image)
+        .arg("--out")
+        .arg(&out)
+        .args(["--format", "mkbootimg"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(!out.join("kernel").exists());
+    assert_eq!(fs::read(out.join("ramdisk")).unwrap(), [0xaa; 100]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.contains("--kernel "), "{stdout}");
+}
+
+#[test]
+fn max_section_size() {
+    let dir = test_dir("max_section_size");
+    let image = dir.join("boot.img");
+    let mut data = v0_image(3000, 4096, [0; 4]);
+    data.resize(2 * 4096, 0);
+    fs::write(&image, &data).unwrap();
+
+    let output = unpack_bootimg()
+        .arg("--boot_img")
+        .arg(&image)
+        .arg("--out")
+        .arg(dir.join("out"))
+        .args(["--max-section-size", "1024"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(4));
+    assert!(!dir.join("out").exists());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("kernel size 3000 exceeds the maximum of 1024"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn offsets() {
+    let dir = test_dir("offsets");
+    let image = dir.join("boot.img");
+    let mut data = v0_image(3000, 4096, [0; 4]);
+    data.resize(3 * 4096, 0);
+    fs::write(&image, &data).unwrap();
+
+    let output = unpack_bootimg()
+        .arg("--boot_img")
+        .arg(&image)
+        .arg("--out")
+        .arg(dir.join("out"))
+        .arg("--offsets")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(!dir.join("out").exists());
+    let hdr = Header::parse(&mut Cursor::new(&data)).unwrap();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("kernel {0} 0x{0:x} 3000 0xbb8\n", hdr.kernel_position())
+    );
+}
+
+#[test]
+fn out_dir() {
+    let dir = test_dir("out_dir");
+    let image = dir.join("boot.img");
+    let mut data = v0_image(3000, 4096, [0; 4]);
+    data.resize(2 * 4096, 0);
+    fs::write(&image, &data).unwrap();
+
+    let output = unpack_bootimg()
+        .arg("--boot_img")
+        .arg(&image)
+        .arg("--out")
+        .arg(&image)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("is an existing file"), "{stderr}");
+
+    // Defaults to the image's file stem
+    let status = unpack_bootimg()
+        .current_dir(&dir)
+        .args(["--boot_img", "boot.img", "-q"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(fs::read(dir.join("boot/kernel")).unwrap().len(), 3000);
+
+    // An image without an extension doesn't collide with its output directory
+    fs::write(dir.join("recovery"), &data).unwrap();
+    let status = unpack_bootimg()
+        .current_dir(&dir)
+        .args(["--boot_img", "recovery", "-q"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(
+        fs::read(dir.join("recovery_unpacked/kernel"))
+            .unwrap()
+            .len(),
+        3000
+    );
+}
+
+#[test]
+fn second_v1_v2() {
+    for version in [1u32, 2] {
+        let dir = test_dir(&format!("second_v{version}"));
+        let image = dir.join("boot.img");
+        let mut data = b"ANDROID!".to_vec();
+        for field in [3000u32, 0, 100, 0, 500, 0, 0, 2048, version] {
+            data.extend(field.to_le_bytes());
+        }
+        data.resize(1644, 0);
+        data.extend([1648u32, 1660][version as usize - 1].to_le_bytes());
+        data.resize(5 * 2048, 0);
+        // The header, kernel and ramdisk take 1, 2 and 1 pages
+        data[4 * 2048..][..500].fill(0xaa);
+        fs::write(&image, &data).unwrap();
+
+        let out = dir.join("out");
+        let status = unpack_bootimg()
+            .arg("--boot_img")
+            .arg(&image)
+            .arg("--out")
+            .arg(&out)
+            .arg("-q")
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(fs::read(out.join("second")).unwrap(), [0xaa; 500]);
+        assert_eq!(fs::read(out.join("kernel")).unwrap().len(), 3000);
+    }
+}
+
+#[test]
+fn trailing_tag() {
+    let dir = test_dir("trailing_tag");
+    let image = dir.join("boot.img");
+    let mut data = v0_image(3000, 4096, [0; 4]);
+    data.resize(2 * 4096, 0);
+    data.extend(b"SEANDROIDENFORCE");
+    fs::write(&image, &data).unwrap();
+
+    let output = unpack_bootimg()
+        .arg("--boot_img")
+        .arg(&image)
+        .arg("--out")
+        .arg(dir.join("out"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("trailing tag: SEANDROIDENFORCE\n"),
+        "{stdout}"
+    );
+    let manifest = fs::read_to_string(dir.join("out/manifest.json")).unwrap();
+    assert!(
+        manifest.contains("\"trailing_tag\": \"se_android_enforce\""),
+        "{manifest}"
+    );
+}
+
+#[test]
+fn truncated_section() {
+    let dir = test_dir("truncated_section");
+    let image = dir.join("boot.img");
+    let mut data = v0_image(3000, 4096, [0; 4]);
+    data.resize(4096 + 1000, 0);
+    fs::write(&image, &data).unwrap();
+
+    let output = unpack_bootimg()
+        .arg("--boot_img")
+        .arg(&image)
+        .arg("--out")
+        .arg(dir.join("out"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(4));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("kernel is truncated: the image has 1000 of its 3000 bytes"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn vendor_ramdisk_fragments() {
+    let dir = test_dir("vendor_ramdisk_fragments");
+    let image = dir.join("vendor_boot.img");
+    let mut data = b"VNDRBOOT".to_vec();
+    for field in [4, 4096, 0x10008000, 0x11000000, 300] {
+        data.extend(u32::to_le_bytes(field));
+    }
+    data.resize(data.len() + 2048, 0);
+    data.extend(0x10000100u32.to_le_bytes());
+    data.extend([0; 16]);
+    data.extend(2128u32.to_le_bytes());
+    data.extend(0u32.to_le_bytes());
+    data.extend(0x1_1f00_0000u64.to_le_bytes());
+    let fragments = [
+        (100, 0, 1, "platform"),
+        (200, 100, 3, "dlkm"),
+        // Duplicate, colliding with the next name once suffixed
+        (50, 0, 3, "dlkm"),
+        (50, 100, 3, "dlkm_2"),
+        (10, 290, 0, ""),
+    ];
+    for field in [fragments.len() as u32 * 108, fragments.len() as u32, 108, 0] {
+        data.extend(u32::to_le_bytes(field));
+    }
+    data.resize(4096, 0);
+    data.extend([1; 100]);
+    data.extend([2; 200]);
+    data.resize(8192, 0);
+    for (size, offset, kind, name) in fragments {
+        for field in [size, offset, kind] {
+            data.extend(u32::to_le_bytes(field));
+        }
+        let mut name = name.as_bytes().to_vec();
+        name.resize(32, 0);
+        data.extend(name);
+        data.extend([0; 64]);
+    }
+    data.resize(12288, 0);
+    fs::write(&image, data).unwrap();
+
+    let out = dir.join("out");
+    let output = unpack_bootimg()
+        .arg("--boot_img")
+        .arg(&image)
+        .arg("--out")
+        .arg(&out)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read(out.join("vendor_ramdisk_platform")).unwrap(),
+        [1; 100]
+    );
+    assert_eq!(fs::read(out.join("vendor_ramdisk_dlkm")).unwrap(), [2; 200]);
+    assert_eq!(
+        fs::read(out.join("vendor_ramdisk_dlkm_2")).unwrap(),
+        [1; 50]
+    );
+    assert_eq!(
+        fs::read(out.join("vendor_ramdisk_dlkm_2_3")).unwrap(),
+        [2; 50]
+    );
+    assert_eq!(fs::read(out.join("vendor_ramdisk_4")).unwrap(), [2; 10]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains(
+            "vendor ramdisk fragment 0: vendor_ramdisk_platform\n\
+             vendor ramdisk fragment 1: vendor_ramdisk_dlkm\n\
+             vendor ramdisk fragment 2: vendor_ramdisk_dlkm_2\n\
+             vendor ramdisk fragment 3: vendor_ramdisk_dlkm_2_3\n\
+             vendor ramdisk fragment 4: vendor_ramdisk_4\n"
+        ),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn avb_footer_mismatch() {
+    let dir = test_dir("avb_footer_mismatch");
+    let image = dir.join("boot.img");
+    // v4 header with an empty kernel and ramdisk and a one-page boot signature,
+    // which ends at 8192
+    let mut data = b"ANDROID!".to_vec();
+    for field in [0, 0, 0, 1584] {
+        data.extend(u32::to_le_bytes(field));
+    }
+    data.resize(0x28, 0);
+    data.extend(4u32.to_le_bytes());
+    data.resize(1580, 0);
+    data.extend(4096u32.to_le_bytes());
+    data.resize(3 * 4096 - 64, 0);
+    // AVB footer placing the vbmeta image within the signature
+    data.extend(b"AVBf");
+    data.extend(1u32.to_be_bytes());
+    data.extend(0u32.to_be_bytes());
+    for field in [4096u64, 4096, 100] {
+        data.extend(field.to_be_bytes());
+    }
+    data.resize(3 * 4096, 0);
+    fs::write(&image, data).unwrap();
+
+    let output = unpack_bootimg()
+        .arg("--boot_img")
+        .arg(&image)
+        .arg("--out")
+        .arg(dir.join("out"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(4));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(
+            "boot signature ends at 8192, but the AVB footer records an original image size \
+             of 4096 and vbmeta at 4096"
+        ),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn mkbootimg_base() {
+    let dir = test_dir("mkbootimg_base");
+    let image = dir.join("boot.img");
+    let mut data = v0_image(0, 4096, [0x10008000, 0x11000000, 0x10f00000, 0x10000100]);
+    // Ramdisk size
+    data[0x10..][..4].copy_from_slice(&100u32.to_le_bytes());
+    data.resize(2 * 4096, 0xaa);
+    fs::write(&image, &data).unwrap();
+
+    let mkbootimg_args = |mode: &str| {
+        let output = unpack_bootimg()
+            .arg("--boot_img")
+            .arg(&image)
+            .arg("--out")
+            .arg(dir.join("out"))
+            .args(["--format", "mkbootimg", mode])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let raw = mkbootimg_args("--mkbootimg-raw");
+    assert!(
+        raw.contains(
+            "--base 0x00000000 --kernel_offset 0x10008000 --ramdisk_offset 0x11000000 \
+             --second_offset 0x10f00000 --tags_offset 0x10000100 "
+        ),
+        "{raw}"
+    );
+    let based = mkbootimg_args("--mkbootimg-base");
+    assert!(
+        based.contains(
+            "--base 0x10000000 --kernel_offset 0x00008000 --ramdisk_offset 0x01000000 \
+             --second_offset 0x00f00000 --tags_offset 0x00000100 "
+        ),
+        "{based}"
+    );
+
+    let output = unpack_bootimg()
+        .arg("--boot_img")
+        .arg(&image)
+        .arg("--out")
+        .arg(dir.join("out"))
+        .arg("--mkbootimg-base")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("--mkbootimg-base and --mkbootimg-raw need --format mkbootimg"),
+        "{stderr}"
+    );
+}